@@ -1,4 +1,4 @@
-use std::{fs::create_dir_all, process::exit, rc::Rc};
+use std::{collections::HashMap, fs::create_dir_all, process::exit, rc::Rc};
 
 use clap::{Parser, Subcommand};
 use miette::{Context, Error, IntoDiagnostic, Result};
@@ -28,6 +28,10 @@ enum Commands {
         #[clap(short = 'p', long)]
         show_picker: bool,
 
+        /// Set a config variable, can be repeated.
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+
         /// Skip the startup commands
         #[clap(long)]
         skip_cmds: bool,
@@ -46,6 +50,10 @@ enum Commands {
         #[clap(short, long)]
         muxer: Option<Muxer>,
 
+        /// Set a config variable, can be repeated.
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+
         /// Skip the shutdown commands
         #[clap(long)]
         skip_cmds: bool,
@@ -101,20 +109,34 @@ impl Cli {
                 file,
                 muxer,
                 show_picker,
+                vars,
                 skip_cmds,
                 skip_attach,
             } => self
                 .session(muxer)?
-                .start(name, file, *show_picker, *skip_cmds, *skip_attach)
+                .start(
+                    name,
+                    file,
+                    &vars.iter().cloned().collect(),
+                    *show_picker,
+                    *skip_cmds,
+                    *skip_attach,
+                )
                 .wrap_err("Could not start session!".to_string()),
             Commands::Stop {
                 name,
                 muxer,
+                vars,
                 skip_cmds: skip_shutdown_cmds,
                 all: stop_all,
             } => self
                 .session(muxer)?
-                .stop(name, *skip_shutdown_cmds, *stop_all)
+                .stop(
+                    name,
+                    &vars.iter().cloned().collect(),
+                    *skip_shutdown_cmds,
+                    *stop_all,
+                )
                 .wrap_err("Unable to stop session(s)!"),
             Commands::List { muxer } => {
                 let session: Vec<String> = self
@@ -175,13 +197,25 @@ impl Cli {
         println!();
         println!("{:?}", error);
         println!();
-        if let Commands::Start { name, muxer, .. } = &self.commands {
+        if let Commands::Start {
+            name, muxer, vars, ..
+        } = &self.commands
+        {
             if let Some(n) = name {
                 log::warn!("Shutting down session: {}", n);
-                let _ = self.session(muxer).unwrap().stop(name, true, false);
+                let vars: HashMap<String, String> = vars.iter().cloned().collect();
+                let _ = self.session(muxer).unwrap().stop(name, &vars, true, false);
             } else {
                 log::warn!("No tmux session to shut down!");
             }
         }
     }
 }
+
+pub(crate) fn parse_var(input: &str) -> Result<(String, String), String> {
+    input
+        .split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid variable '{}', expected KEY=VALUE", input))
+}
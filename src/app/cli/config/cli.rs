@@ -3,7 +3,10 @@ use std::rc::Rc;
 use clap::{Args, Subcommand};
 use miette::Result;
 
-use crate::{app::ConfigManager, common::cmd::ShellRunner};
+use crate::{
+    app::{cli::command_line::parse_var, ConfigManager},
+    common::{cmd::ShellRunner, config::format::Format},
+};
 
 #[derive(Clone, Subcommand, Debug)]
 pub enum Commands {
@@ -15,6 +18,10 @@ pub enum Commands {
         /// Existing configuration to copy from.
         #[clap(short, long)]
         copy: Option<String>,
+
+        /// Format of the new configuration, copies keep the format of their source.
+        #[clap(long, value_enum, default_value_t, conflicts_with = "copy")]
+        format: Format,
     },
 
     /// Edit laio configuration.
@@ -41,6 +48,80 @@ pub enum Commands {
         /// Specify the config file to use.
         #[clap(short, long, default_value = ".laio.yaml")]
         file: String,
+
+        /// Set a config variable, can be repeated.
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+    },
+
+    /// Migrate laio configuration to the current config version, keeping a backup.
+    Migrate {
+        /// Name of the configuration to migrate, omit to migrate local .laio.yaml.
+        #[clap(conflicts_with = "all")]
+        name: Option<String>,
+
+        /// Specify the config file to use.
+        #[clap(short, long, default_value = ".laio.yaml")]
+        file: String,
+
+        /// Migrate all configurations in the laio config directory.
+        #[clap(short, long)]
+        all: bool,
+    },
+
+    /// Format laio configuration, keeping comments where possible.
+    Fmt {
+        /// Name of the configuration to format, omit to format local .laio.yaml.
+        #[clap(conflicts_with = "all")]
+        name: Option<String>,
+
+        /// Specify the config file to use.
+        #[clap(short, long, default_value = ".laio.yaml")]
+        file: String,
+
+        /// Format all configurations in the laio config directory.
+        #[clap(short, long)]
+        all: bool,
+
+        /// Only check the formatting, fails if a configuration would change.
+        #[clap(long)]
+        check: bool,
+    },
+
+    /// Check laio configuration for problems validation does not catch.
+    Lint {
+        /// Name of the configuration to lint, omit to lint local .laio.yaml.
+        name: Option<String>,
+
+        /// Specify the config file to use.
+        #[clap(short, long, default_value = ".laio.yaml")]
+        file: String,
+
+        /// Set a config variable, can be repeated.
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+    },
+
+    /// Draw the tmux layout of laio configuration without starting a session.
+    Preview {
+        /// Name of the configuration to preview, omit to preview local .laio.yaml.
+        name: Option<String>,
+
+        /// Specify the config file to use.
+        #[clap(short, long, default_value = ".laio.yaml")]
+        file: String,
+
+        /// Set a config variable, can be repeated.
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+
+        /// Width of the windows in cells, defaults to the terminal width.
+        #[clap(long)]
+        width: Option<usize>,
+
+        /// Height of the windows in cells, defaults to the terminal height.
+        #[clap(long)]
+        height: Option<usize>,
     },
 
     /// Delete laio configuration.
@@ -57,6 +138,9 @@ pub enum Commands {
     /// List all laio configurations.
     #[clap(alias = "ls")]
     List,
+
+    /// Print the JSON Schema of the laio configuration format.
+    Schema,
 }
 
 /// Manage Configurations
@@ -72,16 +156,36 @@ impl Cli {
         let cfg = ConfigManager::new(config_path, Rc::new(ShellRunner::new()));
 
         match &self.commands {
-            Commands::Create { name, copy } => cfg.create(name, copy),
+            Commands::Create { name, copy, format } => cfg.create(name, copy, *format),
             Commands::Edit { name } => cfg.edit(name),
             Commands::Link { name, file } => cfg.link(name, file),
-            Commands::Validate { name, file } => cfg.validate(name, file),
+            Commands::Validate { name, file, vars } => {
+                cfg.validate(name, file, &vars.iter().cloned().collect())
+            }
+            Commands::Migrate { name, file, all } => cfg.migrate(name, file, *all),
+            Commands::Fmt {
+                name,
+                file,
+                all,
+                check,
+            } => cfg.fmt(name, file, *all, *check),
+            Commands::Lint { name, file, vars } => {
+                cfg.lint(name, file, &vars.iter().cloned().collect())
+            }
+            Commands::Preview {
+                name,
+                file,
+                vars,
+                width,
+                height,
+            } => cfg.preview(name, file, &vars.iter().cloned().collect(), *width, *height),
             Commands::Delete { name, force } => cfg.delete(name, *force),
             Commands::List => {
                 let list = cfg.list()?;
                 println!("{}", list.join("\n"));
                 Ok(())
             }
+            Commands::Schema => cfg.schema(),
         }
     }
 }
@@ -1,12 +1,24 @@
-use crate::common::{cmd::Type, config::Session};
-use miette::{Context, Error, IntoDiagnostic, Result};
+use crate::common::{
+    cmd::Type,
+    config::{
+        format::{append_extension, config_file, is_config, with_extension, Format},
+        lint::LintReport,
+        normalize::{normalize, with_comments},
+        version::{migrate, VERSION},
+        Session,
+    },
+};
+use crate::muxer::tmux::preview::preview;
+use miette::{bail, Context, Error, IntoDiagnostic, Result};
 use std::{
+    collections::HashMap,
     env::{self, var},
     fs::{self},
     io::stdin,
-    path::PathBuf,
+    path::{Path, PathBuf},
     rc::Rc,
 };
+use termion::terminal_size;
 
 use crate::{
     cmd_forget,
@@ -33,22 +45,37 @@ impl<R: Runner> ConfigManager<R> {
         }
     }
 
-    pub(crate) fn create(&self, name: &Option<String>, copy: &Option<String>) -> Result<()> {
+    pub(crate) fn create(
+        &self,
+        name: &Option<String>,
+        copy: &Option<String>,
+        format: Format,
+    ) -> Result<()> {
         let current_path = name
             .as_ref()
             .map(|_| current_working_path())
             .unwrap_or(Ok(".".into()))?;
 
+        // copies keep the format of their source
+        let source = copy.as_ref().map(|copy_name| self.config_file(copy_name));
+        let extension = match &source {
+            Some(source) => source
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or(format.extension()),
+            None => format.extension(),
+        };
+
         let config_file = match name {
             Some(name) => {
-                format!("{}/{}.yaml", self.config_path, name)
+                format!("{}/{}.{}", self.config_path, name, extension)
             }
-            None => ".laio.yaml".to_string(),
+            None => format!(".laio.{}", extension),
         };
 
-        match copy {
-            Some(copy_name) => {
-                let source = format!("{}/{}.yaml", self.config_path, copy_name);
+        match source {
+            Some(source) => {
+                let source = source.to_string_lossy();
                 let _: () = self
                     .cmd_runner
                     .run(&cmd_forget!("cp {} {}", source, config_file))
@@ -58,6 +85,16 @@ impl<R: Runner> ConfigManager<R> {
                 let template = TEMPLATE
                     .replace("{ name }", name.as_deref().unwrap_or("changeme"))
                     .replace("{ path }", &current_path.to_string_lossy());
+                let template = match format {
+                    Format::Yaml => template,
+                    _ => format
+                        .serialize(
+                            &Format::Yaml
+                                .parse(&template)
+                                .map_err(|e| miette::Report::new(*e))?,
+                        )
+                        .wrap_err(format!("Could not convert template to {:?}", format))?,
+                };
                 let _: () = self
                     .cmd_runner
                     .run(&cmd_forget!("echo '{}' > {}", template, config_file))
@@ -72,18 +109,27 @@ impl<R: Runner> ConfigManager<R> {
 
     pub(crate) fn edit(&self, name: &str) -> Result<()> {
         self.cmd_runner.run(&cmd_forget!(
-            "{} {}/{}.yaml",
+            "{} {}",
             var("EDITOR").unwrap_or_else(|_| "vim".to_string()),
-            self.config_path,
-            name
+            self.config_file(name).to_string_lossy()
         ))
     }
 
     pub(crate) fn link(&self, name: &str, file: &str) -> Result<()> {
-        let source = to_absolute_path(file)
-            .wrap_err(format!("Failed to get absolute path for '{}'", file))?;
+        let source = with_extension(
+            &to_absolute_path(file)
+                .wrap_err(format!("Failed to get absolute path for '{}'", file))?,
+        );
         let source_file = source.to_string_lossy();
-        let destination = format!("{}/{}.yaml", self.config_path, name);
+        let destination = format!(
+            "{}/{}.{}",
+            self.config_path,
+            name,
+            source
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or(Format::Yaml.extension())
+        );
         self.cmd_runner
             .run(&cmd_forget!(
                 "ln -s \"{}\" \"{}\"",
@@ -96,16 +142,140 @@ impl<R: Runner> ConfigManager<R> {
             ))
     }
 
-    pub(crate) fn validate(&self, name: &Option<String>, file: &str) -> Result<()> {
+    pub(crate) fn validate(
+        &self,
+        name: &Option<String>,
+        file: &str,
+        vars: &HashMap<String, String>,
+    ) -> Result<()> {
         let config = match name {
-            Some(name) => format!("{}/{}.yaml", &self.config_path, name),
-            None => PathBuf::from(&file)
+            Some(name) => self.config_file(name),
+            None => with_extension(Path::new(file))
                 .canonicalize()
-                .map_err(|_e| Error::msg(format!("Failed to read config: {}.", file)))?
-                .to_string_lossy()
-                .into_owned(),
+                .map_err(|_e| Error::msg(format!("Failed to read config: {}.", file)))?,
         };
-        let _ = Session::from_config(&PathBuf::from(&config)).wrap_err("Validation error!")?;
+        let session = Session::from_config(&config, Path::new(&self.config_path), vars)
+            .wrap_err("Validation error!")?;
+        let format = Format::from_path(&config);
+        let resolved = format.serialize(&session).wrap_err(format!(
+            "Unable to generate {:?} representation of resolved config.",
+            format
+        ))?;
+        println!("{}", resolved);
+        Ok(())
+    }
+
+    /// Rewrites configs of an older version to the current one, keeping the
+    /// original next to it as `<config>.bak`.
+    pub(crate) fn migrate(&self, name: &Option<String>, file: &str, all: bool) -> Result<()> {
+        let failed = self
+            .select(name, file, all)?
+            .into_iter()
+            .filter_map(|config| match migrate_config(&config) {
+                Ok(()) => None,
+                Err(e) => {
+                    log::error!("{:?}", e);
+                    Some(config.to_string_lossy().to_string())
+                }
+            })
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            bail!("Failed to migrate {}", failed.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Rewrites configs in their canonical form, with `check` only reports the
+    /// configs that would change.
+    pub(crate) fn fmt(
+        &self,
+        name: &Option<String>,
+        file: &str,
+        all: bool,
+        check: bool,
+    ) -> Result<()> {
+        let mut unformatted = vec![];
+        for config in self.select(name, file, all)? {
+            let content = fs::read_to_string(&config)
+                .into_diagnostic()
+                .wrap_err(format!("Failed to read config {:?}", config))?;
+            let formatted = normalize(&config, &content)?;
+            if formatted == content {
+                continue;
+            }
+            let config = config.to_string_lossy().to_string();
+            if check {
+                println!("Would reformat {}", config);
+            } else {
+                fs::write(&config, formatted)
+                    .into_diagnostic()
+                    .wrap_err(format!("Failed to write config {:?}", config))?;
+                println!("Formatted {}", config);
+            }
+            unformatted.push(config);
+        }
+        if check && !unformatted.is_empty() {
+            bail!("{} not formatted", unformatted.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Reports what the rules of [`Session::lint`] find, fails if any of it is an error.
+    pub(crate) fn lint(
+        &self,
+        name: &Option<String>,
+        file: &str,
+        vars: &HashMap<String, String>,
+    ) -> Result<()> {
+        let config = self.select(name, file, false)?.remove(0);
+        let (session, source, resolved) =
+            Session::from_config_with_source(&config, Path::new(&self.config_path), vars)
+                .wrap_err("Validation error!")?;
+        let findings = session.lint();
+        if findings.is_empty() {
+            println!("No problems found in {}.", config.to_string_lossy());
+            return Ok(());
+        }
+
+        let report = LintReport::new(&config, source, &resolved, findings);
+        if report.has_errors() {
+            return Err(report.into());
+        }
+        eprintln!("{:?}", miette::Report::new(report));
+        Ok(())
+    }
+
+    /// Prints the tmux layout of each window without starting tmux, at the size
+    /// of the terminal unless given.
+    pub(crate) fn preview(
+        &self,
+        name: &Option<String>,
+        file: &str,
+        vars: &HashMap<String, String>,
+        width: Option<usize>,
+        height: Option<usize>,
+    ) -> Result<()> {
+        let config = self.select(name, file, false)?.remove(0);
+        let session = Session::from_config(&config, Path::new(&self.config_path), vars)
+            .wrap_err("Validation error!")?;
+        let (width, height) = match (width, height) {
+            (Some(width), Some(height)) => (width, height),
+            _ => {
+                let (columns, rows) = terminal_size()
+                    .into_diagnostic()
+                    .wrap_err("Failed to get the terminal size, use --width and --height")?;
+                (
+                    width.unwrap_or(columns.into()),
+                    height.unwrap_or(rows.into()),
+                )
+            }
+        };
+        print!("{}", preview(&session, width, height));
+        Ok(())
+    }
+
+    pub(crate) fn schema(&self) -> Result<()> {
+        println!("{}", Session::json_schema()?);
         Ok(())
     }
 
@@ -119,10 +289,10 @@ impl<R: Runner> ConfigManager<R> {
                 return Ok(());
             }
         }
-        let file = format!("{}/{}.yaml", &self.config_path, name);
+        let file = self.config_file(name);
         fs::remove_file(&file)
             .into_diagnostic()
-            .wrap_err(format!("Failed to delete '{}'", &file))?;
+            .wrap_err(format!("Failed to delete {:?}", &file))?;
         Ok(())
     }
 
@@ -135,7 +305,7 @@ impl<R: Runner> ConfigManager<R> {
             ))?
             .filter_map(|entry| entry.ok())
             .map(|entry| entry.path())
-            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("yaml"))
+            .filter(|path| is_config(path))
             .filter_map(|path| {
                 path.file_stem()
                     .and_then(|name| name.to_str())
@@ -144,6 +314,65 @@ impl<R: Runner> ConfigManager<R> {
             .collect::<Vec<String>>();
 
         entries.sort();
+        entries.dedup();
         Ok(entries)
     }
+
+    // Configs of commands taking a config name, a local file or all configs.
+    fn select(&self, name: &Option<String>, file: &str, all: bool) -> Result<Vec<PathBuf>> {
+        Ok(match (all, name) {
+            (true, _) => self
+                .list()?
+                .iter()
+                .map(|name| self.config_file(name))
+                .collect(),
+            (false, Some(name)) => vec![self.config_file(name)],
+            (false, None) => vec![with_extension(Path::new(file))],
+        })
+    }
+
+    fn config_file(&self, name: &str) -> PathBuf {
+        config_file(Path::new(&self.config_path), name)
+    }
+}
+
+fn migrate_config(config: &Path) -> Result<()> {
+    let content = fs::read_to_string(config)
+        .into_diagnostic()
+        .wrap_err(format!("Failed to read config {:?}", config))?;
+    let format = Format::from_path(config);
+    let original = format
+        .parse(&content)
+        .map_err(|e| miette::Report::new(*e))
+        .wrap_err(format!("Failed to parse config {:?}", config))?;
+
+    let mut migrated = original.clone();
+    let deprecations =
+        migrate(&mut migrated).wrap_err(format!("Failed to migrate config {:?}", config))?;
+    if migrated == original {
+        println!("{} is up to date.", config.to_string_lossy());
+        return Ok(());
+    }
+
+    let backup = append_extension(config, "bak");
+    fs::copy(config, &backup)
+        .into_diagnostic()
+        .wrap_err(format!("Failed to back up config to {:?}", backup))?;
+    fs::write(
+        config,
+        with_comments(&format, &content, &format.serialize(&migrated)?),
+    )
+    .into_diagnostic()
+    .wrap_err(format!("Failed to write config {:?}", config))?;
+
+    println!(
+        "Migrated {} to version {}, the original is kept in {}.",
+        config.to_string_lossy(),
+        VERSION,
+        backup.to_string_lossy()
+    );
+    for deprecation in deprecations {
+        println!("  - {}", deprecation);
+    }
+    Ok(())
 }
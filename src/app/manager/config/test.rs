@@ -1,13 +1,18 @@
 use crate::{
     app::{manager::config::manager::TEMPLATE, ConfigManager},
-    common::cmd::{
-        test::{MockCmdBoolMock, MockCmdStringMock, MockCmdUnitMock, RunnerMock},
-        Type,
+    common::{
+        cmd::{
+            test::{MockCmdBoolMock, MockCmdStringMock, MockCmdUnitMock, RunnerMock},
+            Type,
+        },
+        config::format::Format,
     },
 };
 
 use std::{
-    env::{set_var, var},
+    collections::HashMap,
+    env::{set_var, temp_dir, var},
+    fs,
     rc::Rc,
 };
 
@@ -37,8 +42,12 @@ fn config_new_copy() {
     });
 
     let cfg = ConfigManager::new("/tmp/laio", Rc::clone(&cmd_runner));
-    cfg.create(&Some(session_name.to_string()), &Some(String::from("bla")))
-        .unwrap();
+    cfg.create(
+        &Some(session_name.to_string()),
+        &Some(String::from("bla")),
+        Format::Yaml,
+    )
+    .unwrap();
 
     let _editor = var("EDITOR").unwrap_or_else(|_| "vim".to_string());
 }
@@ -87,7 +96,7 @@ fn config_new_local() {
     });
 
     let cfg = ConfigManager::new(".", Rc::clone(&cmd_runner));
-    cfg.create(&None, &None).unwrap();
+    cfg.create(&None, &None, Format::Yaml).unwrap();
 }
 
 #[test]
@@ -130,9 +139,13 @@ fn config_validate_no_windows() {
     let config_path = "./src/app/manager/test";
     let cfg = ConfigManager::new(config_path, Rc::clone(&cmd_runner));
 
-    cfg.validate(&Some(session_name.to_string()), ".laio.yaml")
-        .expect_err("Expected missing windows")
-        .to_string();
+    cfg.validate(
+        &Some(session_name.to_string()),
+        ".laio.yaml",
+        &HashMap::new(),
+    )
+    .expect_err("Expected missing windows")
+    .to_string();
 }
 
 #[test]
@@ -151,7 +164,190 @@ fn config_validate_multiple_zoom() {
     let config_path = "./src/app/manager/test";
     let cfg = ConfigManager::new(config_path, Rc::clone(&cmd_runner));
 
-    cfg.validate(&Some(session_name.to_string()), ".laio.yaml")
-        .expect_err("Multiple pane zoom attributes per window detected!")
-        .to_string();
+    cfg.validate(
+        &Some(session_name.to_string()),
+        ".laio.yaml",
+        &HashMap::new(),
+    )
+    .expect_err("Multiple pane zoom attributes per window detected!")
+    .to_string();
+}
+
+#[test]
+fn config_new_local_toml() {
+    set_var("EDITOR", "vim");
+    let mut cmd_unit = MockCmdUnitMock::new();
+    let cmd_string = MockCmdStringMock::new();
+    let cmd_bool = MockCmdBoolMock::new();
+
+    cmd_unit
+        .expect_run()
+        .times(1)
+        .withf(|cmd| {
+            matches!(cmd, Type::Forget(content)
+                if content.starts_with("echo 'version = 1\nname = \"changeme\"\npath = \".\"\n")
+                    && content.contains("[[windows.panes]]")
+                    && content.ends_with("' > .laio.toml"))
+        })
+        .returning(|_| Ok(()));
+    cmd_unit
+        .expect_run()
+        .times(1)
+        .withf(|cmd| matches!(cmd, Type::Forget(content) if content == "vim .laio.toml"))
+        .returning(|_| Ok(()));
+
+    let cmd_runner = Rc::new(RunnerMock {
+        cmd_unit,
+        cmd_string,
+        cmd_bool,
+    });
+
+    let cfg = ConfigManager::new(".", Rc::clone(&cmd_runner));
+    cfg.create(&None, &None, Format::Toml).unwrap();
+}
+
+#[test]
+fn config_list_formats() {
+    let cmd_runner = Rc::new(RunnerMock {
+        cmd_unit: MockCmdUnitMock::new(),
+        cmd_string: MockCmdStringMock::new(),
+        cmd_bool: MockCmdBoolMock::new(),
+    });
+
+    let cfg = ConfigManager::new("./src/common/config/test/formats", Rc::clone(&cmd_runner));
+
+    assert_eq!(cfg.list().unwrap(), vec!["child", "session"]);
+}
+
+#[test]
+fn config_migrate() {
+    let cmd_runner = Rc::new(RunnerMock {
+        cmd_unit: MockCmdUnitMock::new(),
+        cmd_string: MockCmdStringMock::new(),
+        cmd_bool: MockCmdBoolMock::new(),
+    });
+
+    let config_path = temp_dir().join("laio_config_migrate");
+    let _ = fs::remove_dir_all(&config_path);
+    fs::create_dir_all(&config_path).unwrap();
+    let legacy = fs::read_to_string("./src/common/config/test/version/legacy.yaml").unwrap();
+    fs::write(config_path.join("legacy.yaml"), &legacy).unwrap();
+    fs::copy(
+        "./src/common/config/test/valid.yaml",
+        config_path.join("valid.yaml"),
+    )
+    .unwrap();
+
+    let cfg = ConfigManager::new(&config_path.to_string_lossy(), Rc::clone(&cmd_runner));
+    cfg.migrate(&Some("legacy".to_string()), ".laio.yaml", false)
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(config_path.join("legacy.yaml.bak")).unwrap(),
+        legacy
+    );
+    let migrated = fs::read_to_string(config_path.join("legacy.yaml")).unwrap();
+    // comments stay with their lines, also with the renamed ones
+    assert_eq!(
+        migrated,
+        format!(
+            "version: 1\n{}",
+            legacy
+                .replace("commands:", "startup:")
+                .replace("\"hello\"", "hello")
+        )
+    );
+
+    cfg.migrate(&None, ".laio.yaml", true).unwrap();
+    assert_eq!(
+        fs::read_to_string(config_path.join("legacy.yaml")).unwrap(),
+        migrated
+    );
+    assert!(config_path.join("valid.yaml.bak").exists());
+    assert_eq!(cfg.list().unwrap(), vec!["legacy", "valid"]);
+
+    fs::remove_dir_all(&config_path).unwrap();
+}
+
+#[test]
+fn config_fmt_check() {
+    let cmd_runner = Rc::new(RunnerMock {
+        cmd_unit: MockCmdUnitMock::new(),
+        cmd_string: MockCmdStringMock::new(),
+        cmd_bool: MockCmdBoolMock::new(),
+    });
+
+    let config_path = temp_dir().join("laio_config_fmt");
+    let _ = fs::remove_dir_all(&config_path);
+    fs::create_dir_all(&config_path).unwrap();
+    let messy = fs::read_to_string("./src/common/config/test/fmt/messy.yaml").unwrap();
+    fs::write(config_path.join("messy.yaml"), &messy).unwrap();
+
+    let cfg = ConfigManager::new(&config_path.to_string_lossy(), Rc::clone(&cmd_runner));
+    let name = Some("messy".to_string());
+    cfg.fmt(&name, ".laio.yaml", false, true)
+        .expect_err("Expected unformatted config");
+    assert_eq!(
+        fs::read_to_string(config_path.join("messy.yaml")).unwrap(),
+        messy
+    );
+
+    cfg.fmt(&name, ".laio.yaml", false, false).unwrap();
+    cfg.fmt(&None, ".laio.yaml", true, true).unwrap();
+    assert_eq!(
+        fs::read_to_string(config_path.join("messy.yaml")).unwrap(),
+        fs::read_to_string("./src/common/config/test/fmt/formatted.yaml").unwrap()
+    );
+
+    fs::remove_dir_all(&config_path).unwrap();
+}
+
+#[test]
+fn config_lint() {
+    let cmd_runner = Rc::new(RunnerMock {
+        cmd_unit: MockCmdUnitMock::new(),
+        cmd_string: MockCmdStringMock::new(),
+        cmd_bool: MockCmdBoolMock::new(),
+    });
+
+    let cfg = ConfigManager::new("./src/common/config/test/lint", Rc::clone(&cmd_runner));
+
+    cfg.lint(&Some("lint".to_string()), ".laio.yaml", &HashMap::new())
+        .expect_err("Expected lint errors");
+    cfg.lint(
+        &Some("configured".to_string()),
+        ".laio.yaml",
+        &HashMap::new(),
+    )
+    .expect_err("Expected duplicate names to be errors");
+    cfg.lint(&Some("clean".to_string()), ".laio.yaml", &HashMap::new())
+        .unwrap();
+}
+
+#[test]
+fn config_preview() {
+    let cmd_runner = Rc::new(RunnerMock {
+        cmd_unit: MockCmdUnitMock::new(),
+        cmd_string: MockCmdStringMock::new(),
+        cmd_bool: MockCmdBoolMock::new(),
+    });
+
+    let cfg = ConfigManager::new("./src/common/config/test/preview", Rc::clone(&cmd_runner));
+
+    cfg.preview(
+        &Some("preview".to_string()),
+        ".laio.yaml",
+        &HashMap::new(),
+        Some(30),
+        Some(8),
+    )
+    .unwrap();
+    cfg.preview(
+        &Some("missing".to_string()),
+        ".laio.yaml",
+        &HashMap::new(),
+        Some(30),
+        Some(8),
+    )
+    .expect_err("Expected a missing config to fail");
 }
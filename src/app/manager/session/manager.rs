@@ -1,14 +1,22 @@
 use crate::common::muxer::Multiplexer;
 use inquire::Select;
 use miette::{bail, Context, IntoDiagnostic, Result};
-use std::{env, fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    common::config::Session,
+    common::config::{
+        format::{config_file, is_config},
+        Session,
+    },
     common::path::{find_config, resolve_symlink, to_absolute_path},
 };
 
 pub(crate) const LAIO_CONFIG: &str = "LAIO_CONFIG";
+pub(crate) const LAIO_VARS: &str = "LAIO_VARS";
 pub(crate) const LOCAL_CONFIG: &str = ".laio.yaml";
 
 pub(crate) struct SessionManager {
@@ -28,6 +36,7 @@ impl SessionManager {
         &self,
         name: &Option<String>,
         file: &Option<String>,
+        vars: &HashMap<String, String>,
         show_picker: bool,
         skip_cmds: bool,
         skip_attach: bool,
@@ -42,9 +51,9 @@ impl SessionManager {
 
         let config = match name {
             Some(name) => {
-                let config = &format!("{}/{}.yaml", &self.config_path, name).to_string();
-                to_absolute_path(config)
-                    .wrap_err(format!("Could not get absolute path for '{}'", config,))?
+                let config = config_file(Path::new(&self.config_path), name);
+                to_absolute_path(&config.to_string_lossy())
+                    .wrap_err(format!("Could not get absolute path for {:?}", config))?
             }
             None => match file {
                 Some(file) => to_absolute_path(file)
@@ -59,10 +68,11 @@ impl SessionManager {
         let target_config = &resolve_symlink(&config)
             .wrap_err(format!("Could not locate '{}'", config.to_string_lossy()))?;
 
-        let session = Session::from_config(target_config).wrap_err(format!(
-            "Could not load session from '{}'",
-            target_config.to_string_lossy(),
-        ))?;
+        let session = Session::from_config(target_config, Path::new(&self.config_path), vars)
+            .wrap_err(format!(
+                "Could not load session from '{}'",
+                target_config.to_string_lossy(),
+            ))?;
 
         self.multiplexer
             .start(&session, config.to_str().unwrap(), skip_attach, skip_cmds)
@@ -71,11 +81,18 @@ impl SessionManager {
     pub(crate) fn stop(
         &self,
         name: &Option<String>,
+        vars: &HashMap<String, String>,
         skip_cmds: bool,
         stop_all: bool,
     ) -> Result<()> {
         self.multiplexer
-            .stop(name, skip_cmds, stop_all)
+            .stop(
+                name,
+                Path::new(&self.config_path),
+                vars,
+                skip_cmds,
+                stop_all,
+            )
             .wrap_err("Multiplexer failed to stop session(s)".to_string())
     }
 
@@ -103,7 +120,7 @@ impl SessionManager {
                 .into_diagnostic()?
                 .filter_map(|entry| entry.ok())
                 .map(|entry| entry.path())
-                .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("yaml"))
+                .filter(|path| is_config(path))
                 .filter_map(|path| {
                     path.file_stem()
                         .and_then(|name| name.to_str())
@@ -137,11 +154,10 @@ impl SessionManager {
                 .prompt();
 
             match selected {
-                Ok(config) => Ok(Some(PathBuf::from(format!(
-                    "{}/{}.yaml",
-                    &config_path,
-                    config.trim_end_matches(" *")
-                )))),
+                Ok(config) => Ok(Some(config_file(
+                    Path::new(config_path),
+                    config.trim_end_matches(" *"),
+                ))),
                 Err(_) => Ok(None),
             }
         }
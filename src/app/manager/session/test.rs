@@ -1,8 +1,9 @@
 use crate::app::manager::session::SessionManager;
-use crate::common::config::Session;
+use crate::common::config::{version::VERSION, Session};
 use crate::common::muxer::test::MockMultiplexer;
 use crate::common::path::current_working_path;
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Once;
 
 static INIT: Once = Once::new();
@@ -22,14 +23,18 @@ fn session_stop() {
     // Set up expectations for `stop`
     mock_multiplexer
         .expect_stop()
-        .withf(|name, skip_cmds, stop_all| {
-            name.as_deref() == Some("foo") && !*skip_cmds && !*stop_all
+        .withf(|name, config_dir, vars, skip_cmds, stop_all| {
+            name.as_deref() == Some("foo")
+                && config_dir == Path::new("/path/to/config")
+                && vars.is_empty()
+                && !*skip_cmds
+                && !*stop_all
         })
-        .returning(|_, _, _| Ok(()));
+        .returning(|_, _, _, _, _| Ok(()));
 
     let session_manager = SessionManager::new("/path/to/config", Box::new(mock_multiplexer));
 
-    let res = session_manager.stop(&Some("foo".to_string()), false, false);
+    let res = session_manager.stop(&Some("foo".to_string()), &HashMap::new(), false, false);
     assert!(res.is_ok());
 }
 
@@ -77,7 +82,14 @@ fn session_start() {
         Box::new(mock_multiplexer),
     );
 
-    let res = session_manager.start(&Some("valid".to_string()), &None, false, false, false);
+    let res = session_manager.start(
+        &Some("valid".to_string()),
+        &None,
+        &HashMap::new(),
+        false,
+        false,
+        false,
+    );
     assert!(res.is_ok());
 }
 
@@ -90,11 +102,19 @@ fn session_to_yaml() {
     // Set up expectations for `get_session`
     mock_multiplexer.expect_get_session().returning(|| {
         Ok(Session {
+            version: VERSION,
             name: "yaml_test".to_string(),
             path: "/tmp".to_string(),
             startup: vec![],
             shutdown: vec![],
+            pre_start: vec![],
+            post_start: vec![],
+            on_attach: vec![],
+            on_detach: vec![],
             env: HashMap::new(),
+            vars: HashMap::new(),
+            lint: HashMap::new(),
+            extends: None,
             windows: vec![],
         })
     });
@@ -2,6 +2,7 @@ mod model;
 mod shell_runner;
 pub(crate) use model::Cmd;
 pub(crate) use model::Runner;
+pub(crate) use model::Supervision;
 pub(crate) use model::Type;
 pub(crate) use shell_runner::ShellRunner;
 
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use miette::Result;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -5,9 +7,20 @@ pub(crate) enum Type {
     Basic(String),
     Verbose(String),
     Forget(String),
+    /// A verbose command that is killed after its timeout and prefixes its output.
+    Supervised(String, Supervision),
+}
+
+/// How a startup or shutdown command is run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Supervision {
+    /// Kill the command once it runs longer.
+    pub(crate) timeout: Option<Duration>,
+    /// Prefix of output lines, for commands running next to others.
+    pub(crate) prefix: Option<String>,
 }
 
-pub(crate) trait Runner: Cmd<()> + Cmd<String> + Cmd<bool> + Clone {}
+pub(crate) trait Runner: Cmd<()> + Cmd<String> + Cmd<bool> + Clone + Sync {}
 
 pub(crate) trait Cmd<T> {
     fn run(&self, cmd: &Type) -> Result<T>;
@@ -1,12 +1,16 @@
 use super::Cmd;
 use super::Runner;
+use super::Supervision;
 use super::Type;
 use miette::IntoDiagnostic;
-use miette::{bail, Result};
+use miette::{bail, miette, Report, Result};
 use std::{
     fmt,
-    io::{BufRead, BufReader, Write},
-    process::{Command, ExitStatus, Stdio},
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::process::CommandExt,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::{self, sleep},
+    time::{Duration, Instant},
 };
 
 impl fmt::Display for Type {
@@ -15,50 +19,74 @@ impl fmt::Display for Type {
             f,
             "{}",
             match self {
-                Type::Basic(cmd) | Type::Verbose(cmd) | Type::Forget(cmd) => cmd,
+                Type::Basic(cmd)
+                | Type::Verbose(cmd)
+                | Type::Forget(cmd)
+                | Type::Supervised(cmd, _) => cmd,
             },
         )
     }
 }
 const PROMPT_CHAR: &str = "❯";
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 #[derive(Clone, Debug)]
 pub(crate) struct ShellRunner;
 
 impl Runner for ShellRunner {}
 
+struct Output {
+    stdout: String,
+    stderr: String,
+    status: ExitStatus,
+}
+
+impl Output {
+    fn error(&self, cmd: &Type) -> Report {
+        let code = match self.status.code() {
+            Some(code) => format!("exit code {}", code),
+            None => self.status.to_string(),
+        };
+        if self.stderr.is_empty() {
+            miette!("Command failed with {}: {}", code, cmd)
+        } else {
+            miette!("Command failed with {}: {}\n{}", code, cmd, self.stderr)
+        }
+    }
+}
+
 impl Cmd<()> for ShellRunner {
     fn run(&self, cmd: &Type) -> Result<()> {
-        let (output, status) = self.run(cmd)?;
+        let output = self.run(cmd)?;
 
-        if status.success() {
-            log::trace!("Result:() {}", output);
+        if output.status.success() {
+            log::trace!("Result:() {}", output.stdout);
             Ok(())
         } else {
-            bail!("Command failed: {}", cmd)
+            Err(output.error(cmd))
         }
     }
 }
 
 impl Cmd<String> for ShellRunner {
     fn run(&self, cmd: &Type) -> Result<String> {
-        let (output, status) = self.run(cmd)?;
+        let output = self.run(cmd)?;
 
-        if status.success() {
-            log::trace!("Result:<String> {}", output);
-            Ok(output)
+        if output.status.success() {
+            log::trace!("Result:<String> {}", output.stdout);
+            Ok(output.stdout)
         } else {
-            bail!("Command failed: {}", cmd)
+            Err(output.error(cmd))
         }
     }
 }
 
 impl Cmd<bool> for ShellRunner {
     fn run(&self, cmd: &Type) -> Result<bool> {
-        let (output, status) = self.run(cmd)?;
+        let output = self.run(cmd)?;
 
-        log::trace!("Result:<bool> {}", output);
-        Ok(status.success())
+        log::trace!("Result:<bool> {}", output.stdout);
+        Ok(output.status.success())
     }
 }
 
@@ -67,11 +95,18 @@ impl ShellRunner {
         Self {}
     }
 
-    fn run(&self, cmd: &Type) -> Result<(String, ExitStatus)> {
-        let (command_string, is_verbose, should_wait) = match cmd {
-            Type::Basic(c) => (c, false, true),
-            Type::Verbose(c) => (c, true, true),
-            Type::Forget(c) => (c, true, false),
+    fn run(&self, cmd: &Type) -> Result<Output> {
+        let default = Supervision::default();
+        let (command_string, is_verbose, should_wait, supervision) = match cmd {
+            Type::Basic(c) => (c, false, true, &default),
+            Type::Verbose(c) => (c, true, true, &default),
+            Type::Forget(c) => (c, true, false, &default),
+            Type::Supervised(c, supervision) => (c, true, true, supervision),
+        };
+        let timeout = supervision.timeout;
+        let prefix = match &supervision.prefix {
+            Some(prefix) => format!("[{}] ", prefix),
+            None => String::new(),
         };
 
         log::trace!("{}", &command_string);
@@ -82,41 +117,102 @@ impl ShellRunner {
                 .arg(command_string)
                 .status()
                 .into_diagnostic()?;
-            return Ok((String::new(), status));
+            return Ok(Output {
+                stdout: String::new(),
+                stderr: String::new(),
+                status,
+            });
         }
 
         if is_verbose {
-            println!("{} {}", &PROMPT_CHAR, &command_string);
+            println!("{}{} {}", prefix, &PROMPT_CHAR, &command_string);
         }
 
-        let mut command = Command::new("sh")
+        let mut command = Command::new("sh");
+        command
             .arg("-c")
             .arg(command_string)
             .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .into_diagnostic()?;
-
-        let mut buffer = Vec::new();
-        if let Some(o) = command.stdout.take() {
-            let reader = BufReader::new(o);
-
-            for line in reader.lines() {
-                match line {
-                    Ok(line) => {
-                        if is_verbose {
-                            println!("{}", line);
-                        }
-                        writeln!(buffer, "{}", line).into_diagnostic()?;
-                    }
-                    Err(e) => eprintln!("Error: {}", e),
+            .stderr(Stdio::piped());
+        if timeout.is_some() {
+            // a process group of its own, so a timeout kills everything sh started
+            command.process_group(0);
+        }
+        let mut child = command.spawn().into_diagnostic()?;
+
+        let stdout = child.stdout.take().map(|o| {
+            let prefix = prefix.clone();
+            thread::spawn(move || read_lines(o, is_verbose.then_some(prefix), false))
+        });
+        let stderr = child.stderr.take().map(|e| {
+            let prefix = prefix.clone();
+            thread::spawn(move || read_lines(e, is_verbose.then_some(prefix), true))
+        });
+
+        let status = match timeout {
+            Some(timeout) => match wait_timeout(&mut child, timeout)? {
+                Some(status) => status,
+                None => bail!(
+                    "Command timed out after {}s: {}",
+                    timeout.as_secs(),
+                    command_string
+                ),
+            },
+            None => child.wait().into_diagnostic()?,
+        };
+
+        let join = |reader: Option<thread::JoinHandle<Result<String>>>| -> Result<String> {
+            match reader {
+                Some(reader) => reader
+                    .join()
+                    .map_err(|_| miette!("Unable to read output of: {}", command_string))?,
+                None => Ok(String::new()),
+            }
+        };
+        let stdout = join(stdout)?;
+        let stderr = join(stderr)?;
+        log::trace!("Result: {}", stdout);
+        Ok(Output {
+            stdout: stdout.trim().to_string(),
+            stderr: stderr.trim().to_string(),
+            status,
+        })
+    }
+}
+
+/// Collects the output of a command, printing it with `prefix` unless that is `None`.
+fn read_lines(reader: impl Read, prefix: Option<String>, is_stderr: bool) -> Result<String> {
+    let mut buffer = Vec::new();
+    for line in BufReader::new(reader).lines() {
+        match line {
+            Ok(line) => {
+                match &prefix {
+                    Some(prefix) if is_stderr => eprintln!("{}{}", prefix, line),
+                    Some(prefix) => println!("{}{}", prefix, line),
+                    None => {}
                 }
+                writeln!(buffer, "{}", line).into_diagnostic()?;
             }
+            Err(e) => eprintln!("Error: {}", e),
         }
+    }
+    String::from_utf8(buffer).into_diagnostic()
+}
 
-        let status = command.wait().into_diagnostic()?;
-        let output = String::from_utf8(buffer).into_diagnostic()?;
-        log::trace!("Result: {}", output);
-        Ok((output.trim().to_string(), status))
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().into_diagnostic()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            // a negative pid signals the whole process group
+            let _ = Command::new("kill")
+                .args(["-s", "KILL", "--", &format!("-{}", child.id())])
+                .status();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        sleep(POLL_INTERVAL);
     }
 }
@@ -0,0 +1,86 @@
+use std::{
+    env,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::common::path::{sanitize_path, to_absolute_path};
+
+/// All predicates of a condition have to match for a window or pane to be created.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, JsonSchema)]
+pub(crate) struct Condition {
+    /// `NAME` matches if the variable is set, `NAME=value` if it equals value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) env: Option<String>,
+    /// Path relative to the session path or absolute.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) exists: Option<String>,
+    /// Regular expression the full hostname has to match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) hostname: Option<String>,
+    /// Executable that has to be found on PATH.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) command: Option<String>,
+}
+
+impl Condition {
+    pub(crate) fn matches(&self) -> bool {
+        let matches = self.env.as_deref().is_none_or(env_matches)
+            && self
+                .exists
+                .as_deref()
+                .is_none_or(|p| to_absolute_path(p).is_ok_and(|p| p.exists()))
+            && self.hostname.as_deref().is_none_or(hostname_matches)
+            && self.command.as_deref().is_none_or(command_exists);
+        log::trace!("condition {:?} matches: {}", self, matches);
+        matches
+    }
+
+    pub(crate) fn resolve_paths(&mut self, session_path: &String) {
+        if let Some(exists) = &self.exists {
+            self.exists = Some(sanitize_path(exists, session_path));
+        }
+    }
+}
+
+fn env_matches(condition: &str) -> bool {
+    match condition.split_once('=') {
+        Some((name, value)) => env::var(name).is_ok_and(|v| v == value),
+        None => env::var_os(condition).is_some(),
+    }
+}
+
+fn hostname_matches(pattern: &str) -> bool {
+    let Ok(hostname) = hostname::get() else {
+        log::warn!("Unable to determine hostname");
+        return false;
+    };
+    match Regex::new(&format!("^(?:{})$", pattern)) {
+        Ok(re) => re.is_match(&hostname.to_string_lossy()),
+        Err(e) => {
+            log::warn!("Invalid hostname pattern '{}': {}", pattern, e);
+            false
+        }
+    }
+}
+
+/// Whether a command is an executable path or found on PATH.
+pub(crate) fn command_exists(command: &str) -> bool {
+    if command.contains('/') {
+        return is_executable(Path::new(command));
+    }
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths)
+            .map(|dir: PathBuf| dir.join(command))
+            .any(|path| is_executable(&path))
+    })
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
@@ -0,0 +1,167 @@
+use std::{
+    fs::read_to_string,
+    mem::take,
+    path::{Path, PathBuf},
+};
+
+use miette::{bail, Context, IntoDiagnostic, Result};
+use serde_yaml::Value;
+
+use crate::common::{
+    config::{
+        format::{with_extension, Format},
+        include::resolve_includes,
+        validation::ConfigError,
+        version::migrate,
+    },
+    path::to_absolute_path,
+};
+
+const EXTENDS: &str = "extends";
+
+// Loads a config of any format and resolves its `extends` chain into a single yaml value.
+// Scalars and lists of the extending config win, `env` and `vars` are merged
+// key by key and `windows` are merged by name. Named configs that are not next
+// to the extending config are looked up in `config_dir`.
+pub(crate) fn load_config(config: &Path, config_dir: &Path) -> Result<Value> {
+    resolve(config, config, config_dir, &mut vec![])
+}
+
+fn resolve(
+    config: &Path,
+    root: &Path,
+    config_dir: &Path,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Value> {
+    let canonical = config
+        .canonicalize()
+        .into_diagnostic()
+        .wrap_err(format!("Could not locate config {:?}", config))?;
+
+    if chain.contains(&canonical) {
+        chain.push(canonical);
+        bail!(
+            "Cyclic extends detected: {}",
+            chain
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        );
+    }
+
+    let content = read_to_string(config)
+        .into_diagnostic()
+        .wrap_err(format!("Could not read config {:?}", config))?;
+    let mut value = Format::from_path(config)
+        .parse(&content)
+        .map_err(|problem| {
+            ConfigError::new("config", config, content.clone(), vec![*problem], None)
+        })?;
+
+    for deprecation in
+        migrate(&mut value).wrap_err(format!("Could not load config {:?}", config))?
+    {
+        // shown at the default log level, deprecations should not go unnoticed
+        eprintln!(
+            "Warning: {:?}: {}, run `laio config migrate` to update the config.",
+            config, deprecation
+        );
+    }
+    resolve_includes(&mut value, config, root)?;
+
+    let base = match value.as_mapping_mut().and_then(|m| m.remove(EXTENDS)) {
+        Some(Value::String(base)) => base,
+        Some(_) => bail!(
+            "'{}' in {:?} has to be a config name or path",
+            EXTENDS,
+            config
+        ),
+        None => return Ok(value),
+    };
+
+    log::debug!("{:?} extends '{}'", config, base);
+
+    chain.push(canonical);
+    let base_value = resolve(
+        &base_path(&base, config, config_dir)?,
+        root,
+        config_dir,
+        chain,
+    )
+    .wrap_err(format!("Could not extend '{}' in {:?}", base, config))?;
+    chain.pop();
+
+    // the resolved config shows what it extends
+    let mut merged = merge(base_value, value);
+    if let Some(mapping) = merged.as_mapping_mut() {
+        mapping.insert(EXTENDS.into(), base.into());
+    }
+    Ok(merged)
+}
+
+fn base_path(base: &str, config: &Path, config_dir: &Path) -> Result<PathBuf> {
+    if base.starts_with('~') || Path::new(base).is_absolute() {
+        return Ok(with_extension(&to_absolute_path(base)?));
+    }
+
+    let path = with_extension(&config.parent().unwrap_or(Path::new(".")).join(base));
+    if path.exists() {
+        return Ok(path);
+    }
+    log::debug!(
+        "No config {:?}, extending '{}' in {:?}",
+        path,
+        base,
+        config_dir
+    );
+    Ok(with_extension(&config_dir.join(base)))
+}
+
+fn merge(base: Value, value: Value) -> Value {
+    match (base, value) {
+        (Value::Mapping(mut base), Value::Mapping(value)) => {
+            for (key, value) in value {
+                let merged = match (key.as_str(), base.get(&key).cloned()) {
+                    (Some("windows"), Some(base_windows)) => merge_windows(base_windows, value),
+                    (Some("env" | "vars"), Some(base_map)) => merge_maps(base_map, value),
+                    _ => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Mapping(base)
+        }
+        (_, value) => value,
+    }
+}
+
+fn merge_maps(base: Value, value: Value) -> Value {
+    match (base, value) {
+        (Value::Mapping(mut base), Value::Mapping(value)) => {
+            base.extend(value);
+            Value::Mapping(base)
+        }
+        (_, value) => value,
+    }
+}
+
+fn merge_windows(base: Value, value: Value) -> Value {
+    match (base, value) {
+        (Value::Sequence(mut windows), Value::Sequence(value)) => {
+            let name = |window: &Value| window.get("name").cloned();
+
+            for window in value {
+                match windows
+                    .iter()
+                    .position(|w| name(w).is_some() && name(w) == name(&window))
+                {
+                    Some(idx) => windows[idx] = merge(take(&mut windows[idx]), window),
+                    None => windows.push(window),
+                }
+            }
+
+            Value::Sequence(windows)
+        }
+        (_, value) => value,
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+use serde_yaml::Value;
+
+use super::validation::Problem;
+
+// Extensions tried in order when a config is referenced by name only.
+pub(crate) const EXTENSIONS: [&str; 4] = ["yaml", "yml", "toml", "json"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub(crate) enum Format {
+    #[default]
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Detects the format by extension, anything unknown is treated as yaml.
+    pub(crate) fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("json") => Format::Json,
+            _ => Format::Yaml,
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            Format::Json => "json",
+        }
+    }
+
+    pub(crate) fn parse(&self, content: &str) -> std::result::Result<Value, Box<Problem>> {
+        let syntax_error = |message: &dyn Display, offset: Option<usize>| {
+            let offset = offset.map(|offset| offset.min(content.len().saturating_sub(1)));
+            let problem = Problem::new("", message, "here").with_help(format!(
+                "Check the {} syntax around the marked location.",
+                self.extension().to_uppercase()
+            ));
+            Box::new(match offset {
+                Some(offset) => problem.at_offset(offset),
+                None => problem,
+            })
+        };
+        match self {
+            Format::Yaml => serde_yaml::from_str(content)
+                .map_err(|e| syntax_error(&e, e.location().map(|l| l.index()))),
+            Format::Toml => toml::from_str(content)
+                .map_err(|e| syntax_error(&e.message(), e.span().map(|span| span.start))),
+            Format::Json => serde_json::from_str(content).map_err(|e| {
+                let offset = content
+                    .split_inclusive('\n')
+                    .take(e.line().saturating_sub(1))
+                    .map(str::len)
+                    .sum::<usize>()
+                    + e.column().saturating_sub(1);
+                syntax_error(&e, Some(offset))
+            }),
+        }
+    }
+
+    pub(crate) fn serialize<T: Serialize>(&self, value: &T) -> Result<String> {
+        match self {
+            Format::Yaml => serde_yaml::to_string(value).into_diagnostic(),
+            Format::Toml => toml::to_string(value).into_diagnostic(),
+            Format::Json => serde_json::to_string_pretty(value).into_diagnostic(),
+        }
+    }
+}
+
+pub(crate) fn is_config(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext))
+}
+
+/// Returns the first existing `<name>.<ext>` in `dir`, falling back to `<name>.yaml`.
+pub(crate) fn config_file(dir: &Path, name: &str) -> PathBuf {
+    with_extension(&dir.join(name))
+}
+
+/// Returns `path` if it exists, otherwise the first existing variant of it with
+/// a config extension, otherwise `path` itself with a yaml extension if it has none.
+pub(crate) fn with_extension(path: &Path) -> PathBuf {
+    if path.is_file() {
+        return path.to_path_buf();
+    }
+    let base = match is_config(path) {
+        true => path.with_extension(""),
+        false => path.to_path_buf(),
+    };
+    EXTENSIONS
+        .iter()
+        .map(|ext| append_extension(&base, ext))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| match is_config(path) {
+            true => path.to_path_buf(),
+            false => append_extension(path, Format::Yaml.extension()),
+        })
+}
+
+pub(crate) fn append_extension(path: &Path, ext: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(ext);
+    PathBuf::from(path)
+}
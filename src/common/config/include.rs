@@ -0,0 +1,122 @@
+use std::{
+    fs::read_to_string,
+    mem::take,
+    path::{Path, PathBuf},
+};
+
+use miette::{bail, Context, IntoDiagnostic, Result};
+use serde_valid::Validate;
+use serde_yaml::Value;
+
+use crate::common::{
+    config::{
+        format::Format,
+        validation::{deserialize_problem, validation_problems, ConfigError},
+        Window,
+    },
+    path::to_absolute_path,
+};
+
+pub(crate) const INCLUDE: &str = "include";
+
+// Splices the windows of `include: <fragment>` entries into the windows of a config.
+// Fragments are looked up relative to the including config first and the
+// top level config second.
+pub(crate) fn resolve_includes(value: &mut Value, config: &Path, root: &Path) -> Result<()> {
+    let Some(Value::Sequence(windows)) = value.get_mut("windows") else {
+        return Ok(());
+    };
+
+    let mut resolved = Vec::with_capacity(windows.len());
+    for window in take(windows) {
+        match window.get(INCLUDE) {
+            Some(Value::String(fragment)) => {
+                let fragment_path = fragment_path(fragment, config, root)?;
+                log::debug!("{:?} includes {:?}", config, fragment_path);
+                resolved.extend(
+                    load_fragment(&fragment_path)
+                        .wrap_err(format!("Could not include '{}' in {:?}", fragment, config))?,
+                );
+            }
+            Some(_) => bail!(
+                "'{}' in {:?} has to be a path to a fragment",
+                INCLUDE,
+                config
+            ),
+            None => resolved.push(window),
+        }
+    }
+    *windows = resolved;
+
+    Ok(())
+}
+
+fn fragment_path(fragment: &str, config: &Path, root: &Path) -> Result<PathBuf> {
+    if fragment.starts_with('~') || Path::new(fragment).is_absolute() {
+        return to_absolute_path(fragment);
+    }
+
+    let candidates: Vec<PathBuf> = [config, root]
+        .iter()
+        .map(|path| path.parent().unwrap_or(Path::new(".")).join(fragment))
+        .collect();
+
+    match candidates.iter().find(|path| path.exists()) {
+        Some(path) => Ok(path.clone()),
+        None => bail!(
+            "Could not find fragment '{}', tried: {}",
+            fragment,
+            candidates
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn load_fragment(fragment: &Path) -> Result<Vec<Value>> {
+    let content = read_to_string(fragment)
+        .into_diagnostic()
+        .wrap_err(format!("Could not read fragment {:?}", fragment))?;
+    let value = Format::from_path(fragment)
+        .parse(&content)
+        .map_err(|problem| {
+            ConfigError::new("fragment", fragment, content.clone(), vec![*problem], None)
+        })?;
+
+    // path of the windows within the fragment, used to locate problems
+    let (windows, prefix) = match value {
+        Value::Sequence(windows) => (windows, Some("")),
+        Value::Mapping(mut mapping) => match mapping.remove("windows") {
+            Some(Value::Sequence(windows)) => (windows, Some("windows")),
+            Some(_) => bail!("Fragment {:?} has malformed windows", fragment),
+            None => (vec![Value::Mapping(mapping)], None),
+        },
+        _ => bail!(
+            "Fragment {:?} has to contain a window or a list of windows",
+            fragment
+        ),
+    };
+
+    let mut problems = vec![];
+    for (idx, window) in windows.iter().enumerate() {
+        let path = prefix
+            .map(|prefix| format!("{}[{}]", prefix, idx))
+            .unwrap_or_default();
+        match serde_path_to_error::deserialize::<_, Window>(window.clone()) {
+            Ok(window) => {
+                if let Err(errors) = window.validate() {
+                    problems.extend(validation_problems(&errors, &path));
+                }
+            }
+            Err(e) => problems.push(deserialize_problem(&e).prefixed(&path)),
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(ConfigError::new("fragment", fragment, content, problems, None).into());
+    }
+
+    Ok(windows)
+}
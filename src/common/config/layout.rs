@@ -0,0 +1,468 @@
+use std::{fmt::Display, ops::Range};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+
+use super::{FlexDirection, Pane};
+
+/// Tmux layout preset for a flat list of panes.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Layout {
+    /// One large pane on the left, the others stacked on the right.
+    MainVertical,
+    /// One large pane at the top, the others side by side below.
+    MainHorizontal,
+    /// All panes side by side.
+    EvenHorizontal,
+    /// All panes stacked.
+    EvenVertical,
+    /// Panes in rows and columns of about the same size.
+    Tiled,
+}
+
+impl Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Layout::MainVertical => "main-vertical",
+            Layout::MainHorizontal => "main-horizontal",
+            Layout::EvenHorizontal => "even-horizontal",
+            Layout::EvenVertical => "even-vertical",
+            Layout::Tiled => "tiled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Layout {
+    /// Arranges flat panes into the flex tree closest to the preset, for
+    /// multiplexers without the tmux presets.
+    pub(crate) fn arrange(&self, panes: &[Pane]) -> (FlexDirection, Vec<Pane>) {
+        let even = |panes: &[Pane]| -> Vec<Pane> {
+            panes
+                .iter()
+                .map(|pane| Pane {
+                    flex: 1,
+                    ..pane.clone()
+                })
+                .collect()
+        };
+        let main = |direction: FlexDirection, rest: FlexDirection| match panes {
+            [] | [_] => (direction, even(panes)),
+            [main, others @ ..] => (
+                direction,
+                vec![
+                    Pane {
+                        flex: 2,
+                        ..main.clone()
+                    },
+                    Pane::container(rest, 1, even(others)),
+                ],
+            ),
+        };
+
+        match self {
+            Layout::MainVertical => main(FlexDirection::Row, FlexDirection::Column),
+            Layout::MainHorizontal => main(FlexDirection::Column, FlexDirection::Row),
+            Layout::EvenHorizontal => (FlexDirection::Row, even(panes)),
+            Layout::EvenVertical => (FlexDirection::Column, even(panes)),
+            Layout::Tiled => {
+                // grows rows first, like tmux does
+                let (mut rows, mut columns) = (1, 1);
+                while rows * columns < panes.len() {
+                    rows += 1;
+                    if rows * columns < panes.len() {
+                        columns += 1;
+                    }
+                }
+                let rows = panes
+                    .chunks(columns)
+                    .map(|row| match row {
+                        [pane] => Pane {
+                            flex: 1,
+                            ..pane.clone()
+                        },
+                        _ => Pane::container(FlexDirection::Row, 1, even(row)),
+                    })
+                    .collect();
+                (FlexDirection::Column, rows)
+            }
+        }
+    }
+}
+
+/// Grid the panes of a window are placed in, row by row.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Validate, JsonSchema)]
+pub(crate) struct Grid {
+    /// Number of rows.
+    #[validate(minimum = 1, message = "A grid needs at least one row")]
+    #[schemars(range(min = 1))]
+    pub(crate) rows: usize,
+    /// Number of columns.
+    #[validate(minimum = 1, message = "A grid needs at least one column")]
+    #[schemars(range(min = 1))]
+    pub(crate) columns: usize,
+}
+
+/// Rows and columns of a grid a pane takes up.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Validate, JsonSchema)]
+pub(crate) struct Span {
+    /// Number of rows, defaults to 1.
+    #[validate(minimum = 1, message = "A span covers at least one row")]
+    #[schemars(range(min = 1))]
+    #[serde(default = "one")]
+    pub(crate) rows: usize,
+    /// Number of columns, defaults to 1.
+    #[validate(minimum = 1, message = "A span covers at least one column")]
+    #[schemars(range(min = 1))]
+    #[serde(default = "one")]
+    pub(crate) columns: usize,
+}
+
+fn one() -> usize {
+    1
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self {
+            rows: 1,
+            columns: 1,
+        }
+    }
+}
+
+// A region of the grid, split where no pane crosses from one part into the next.
+enum Region {
+    Empty,
+    Leaf(usize),
+    Split(FlexDirection, Vec<(usize, Region)>),
+}
+
+impl Grid {
+    /// Places the panes row by row and compiles the grid into nested flex panes.
+    /// Cells left empty are taken up by their neighbours.
+    pub(crate) fn arrange(&self, panes: &[Pane]) -> Result<(FlexDirection, Vec<Pane>), String> {
+        let cells = self.place(panes)?;
+        let region = self.region(&cells, 0..self.rows, 0..self.columns)?;
+        Ok(match region {
+            Region::Split(direction, parts) => (
+                direction,
+                parts
+                    .into_iter()
+                    .filter_map(|(flex, part)| part.into_pane(flex, panes))
+                    .collect(),
+            ),
+            region => (
+                FlexDirection::Row,
+                region.into_pane(1, panes).into_iter().collect(),
+            ),
+        })
+    }
+
+    fn place(&self, panes: &[Pane]) -> Result<Vec<Vec<Option<usize>>>, String> {
+        let mut cells = vec![vec![None; self.columns]; self.rows];
+        for (idx, pane) in panes.iter().enumerate() {
+            let span = pane.span.unwrap_or_default();
+            let fits = |row: usize, column: usize| {
+                row + span.rows <= self.rows
+                    && column + span.columns <= self.columns
+                    && cells[row..row + span.rows].iter().all(|cells| {
+                        cells[column..column + span.columns]
+                            .iter()
+                            .all(Option::is_none)
+                    })
+            };
+            let (row, column) = (0..self.rows)
+                .flat_map(|row| (0..self.columns).map(move |column| (row, column)))
+                .find(|(row, column)| fits(*row, *column))
+                .ok_or(format!(
+                    "Pane {} does not fit into the {}x{} grid",
+                    idx + 1,
+                    self.rows,
+                    self.columns
+                ))?;
+            for cells in cells[row..row + span.rows].iter_mut() {
+                for cell in cells[column..column + span.columns].iter_mut() {
+                    *cell = Some(idx);
+                }
+            }
+        }
+        Ok(cells)
+    }
+
+    fn region(
+        &self,
+        cells: &[Vec<Option<usize>>],
+        rows: Range<usize>,
+        columns: Range<usize>,
+    ) -> Result<Region, String> {
+        let mut panes: Vec<usize> = rows
+            .clone()
+            .flat_map(|row| cells[row][columns.clone()].iter().flatten().copied())
+            .collect();
+        panes.sort();
+        panes.dedup();
+        match panes[..] {
+            [] => return Ok(Region::Empty),
+            [pane] => return Ok(Region::Leaf(pane)),
+            _ => {}
+        }
+
+        let crosses = |a: Option<usize>, b: Option<usize>| a.is_some() && a == b;
+        let row_cuts: Vec<usize> = (rows.start + 1..rows.end)
+            .filter(|row| {
+                !columns
+                    .clone()
+                    .any(|column| crosses(cells[row - 1][column], cells[*row][column]))
+            })
+            .collect();
+        if !row_cuts.is_empty() {
+            let parts = bounds(&rows, &row_cuts)
+                .into_iter()
+                .map(|part| Ok((part.len(), self.region(cells, part, columns.clone())?)))
+                .collect::<Result<_, String>>()?;
+            return Ok(Region::split(FlexDirection::Column, parts));
+        }
+
+        let column_cuts: Vec<usize> = (columns.start + 1..columns.end)
+            .filter(|column| {
+                !rows
+                    .clone()
+                    .any(|row| crosses(cells[row][column - 1], cells[row][*column]))
+            })
+            .collect();
+        if !column_cuts.is_empty() {
+            let parts = bounds(&columns, &column_cuts)
+                .into_iter()
+                .map(|part| Ok((part.len(), self.region(cells, rows.clone(), part)?)))
+                .collect::<Result<_, String>>()?;
+            return Ok(Region::split(FlexDirection::Row, parts));
+        }
+
+        Err(format!(
+            "Panes {} can not be split into rows and columns",
+            panes
+                .iter()
+                .map(|pane| (pane + 1).to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+}
+
+fn bounds(range: &Range<usize>, cuts: &[usize]) -> Vec<Range<usize>> {
+    let starts = std::iter::once(range.start).chain(cuts.iter().copied());
+    let ends = cuts.iter().copied().chain(std::iter::once(range.end));
+    starts.zip(ends).map(|(start, end)| start..end).collect()
+}
+
+impl Region {
+    // Empty parts are dropped, a single remaining part replaces the split.
+    fn split(direction: FlexDirection, parts: Vec<(usize, Region)>) -> Region {
+        let mut parts: Vec<(usize, Region)> = parts
+            .into_iter()
+            .filter(|(_, part)| !matches!(part, Region::Empty))
+            .collect();
+        match parts.len() {
+            0 => Region::Empty,
+            1 => parts.remove(0).1,
+            _ => Region::Split(direction, parts),
+        }
+    }
+
+    fn into_pane(self, flex: usize, panes: &[Pane]) -> Option<Pane> {
+        match self {
+            Region::Empty => None,
+            Region::Leaf(idx) => Some(Pane {
+                flex,
+                size: None,
+                min_size: None,
+                max_size: None,
+                ..panes[idx].clone()
+            }),
+            Region::Split(direction, parts) => Some(Pane::container(
+                direction,
+                flex,
+                parts
+                    .into_iter()
+                    .filter_map(|(flex, part)| part.into_pane(flex, panes))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// A rectangle of a window in cells, counted from the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Rect {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) x: usize,
+    pub(crate) y: usize,
+}
+
+/// The rectangle of a pane and, for panes with nested panes, the rectangles of
+/// those. Nested rectangles are separated by one cell wide dividers and fill
+/// their parent exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Cell {
+    pub(crate) rect: Rect,
+    pub(crate) direction: FlexDirection,
+    pub(crate) children: Vec<Cell>,
+}
+
+impl Cell {
+    /// Lays out `panes` in `direction` within a `width` x `height` window, if
+    /// the window has room for a cell per pane and the dividers between them.
+    pub(crate) fn solve(
+        panes: &[Pane],
+        direction: &FlexDirection,
+        width: usize,
+        height: usize,
+    ) -> Option<Self> {
+        if width < minimum(panes, direction, &FlexDirection::Row)
+            || height < minimum(panes, direction, &FlexDirection::Column)
+        {
+            return None;
+        }
+        let rect = Rect {
+            width,
+            height,
+            x: 0,
+            y: 0,
+        };
+        Some(Cell {
+            rect,
+            direction: direction.clone(),
+            children: split(panes, direction, rect),
+        })
+    }
+}
+
+fn split(panes: &[Pane], direction: &FlexDirection, rect: Rect) -> Vec<Cell> {
+    let (total, mut offset) = match direction {
+        FlexDirection::Row => (rect.width, rect.x),
+        FlexDirection::Column => (rect.height, rect.y),
+    };
+    let required: Vec<usize> = panes
+        .iter()
+        .map(|pane| minimum(&pane.active_panes(), &pane.flex_direction, direction))
+        .collect();
+    resolve_sizes(panes, total, &required)
+        .into_iter()
+        .zip(panes)
+        .map(|(size, pane)| {
+            let rect = match direction {
+                FlexDirection::Row => Rect {
+                    width: size,
+                    x: offset,
+                    ..rect
+                },
+                FlexDirection::Column => Rect {
+                    height: size,
+                    y: offset,
+                    ..rect
+                },
+            };
+            // the divider takes the cell after each pane
+            offset += size + 1;
+            Cell {
+                rect,
+                direction: pane.flex_direction.clone(),
+                children: split(&pane.active_panes(), &pane.flex_direction, rect),
+            }
+        })
+        .collect()
+}
+
+// Cells along `axis` that `panes` split in `direction` need for a cell each
+// and the dividers between them.
+fn minimum(panes: &[Pane], direction: &FlexDirection, axis: &FlexDirection) -> usize {
+    let nested = panes
+        .iter()
+        .map(|pane| minimum(&pane.active_panes(), &pane.flex_direction, axis));
+    match panes.len() {
+        0 => 1,
+        len if direction == axis => nested.sum::<usize>() + len - 1,
+        _ => nested.max().unwrap_or(1),
+    }
+}
+
+// Resolves pane sizes along the split direction flexbox-style: fixed sizes
+// are taken first, the remaining space is shared by flex and clamped to the
+// min and max sizes, freezing clamped panes until all constraints hold. No
+// pane gets less than it requires, as long as there is room for that.
+pub(crate) fn resolve_sizes(panes: &[Pane], total: usize, required: &[usize]) -> Vec<usize> {
+    let available = total.saturating_sub(panes.len().saturating_sub(1));
+    let clamp = |i: usize, size: usize| {
+        let pane = &panes[i];
+        let size = pane
+            .max_size
+            .map_or(size, |max| size.min(max.resolve(total)));
+        pane.min_size
+            .map_or(size, |min| size.max(min.resolve(total)))
+            .max(required[i])
+    };
+
+    let mut sizes: Vec<Option<usize>> = panes
+        .iter()
+        .enumerate()
+        .map(|(i, pane)| pane.size.map(|size| clamp(i, size.resolve(total))))
+        .collect();
+
+    loop {
+        let flexible: Vec<usize> = (0..panes.len()).filter(|&i| sizes[i].is_none()).collect();
+        if flexible.is_empty() {
+            break;
+        }
+
+        let remaining = available.saturating_sub(sizes.iter().flatten().sum());
+        let flex_total: usize = flexible.iter().map(|&i| panes[i].flex).sum();
+        let shares: Vec<usize> = flexible
+            .iter()
+            .map(|&i| remaining * panes[i].flex / flex_total)
+            .collect();
+
+        let mut clamped = false;
+        for (&i, &share) in flexible.iter().zip(shares.iter()) {
+            if clamp(i, share) != share {
+                sizes[i] = Some(clamp(i, share));
+                clamped = true;
+            }
+        }
+
+        if !clamped {
+            for (&i, &share) in flexible.iter().zip(shares.iter()) {
+                sizes[i] = Some(share);
+            }
+            // rounding leftovers go to the last flexible pane
+            let leftover = remaining - shares.iter().sum::<usize>();
+            if let Some(last) = flexible.last().and_then(|&i| sizes[i].as_mut()) {
+                *last += leftover;
+            }
+            break;
+        }
+    }
+
+    let mut sizes: Vec<usize> = sizes.into_iter().map(Option::unwrap_or_default).collect();
+
+    // the panes have to fill the window, grow the last pane or shrink from the end
+    let used: usize = sizes.iter().sum();
+    if used < available {
+        if let Some(last) = sizes.last_mut() {
+            *last += available - used;
+        }
+    } else {
+        let mut excess = used - available;
+        for (size, required) in sizes.iter_mut().zip(required).rev() {
+            let shrink = excess.min(size.saturating_sub(*required));
+            *size -= shrink;
+            excess -= shrink;
+        }
+    }
+
+    log::trace!("resolved sizes: {:?} of {}", sizes, total);
+    sizes
+}
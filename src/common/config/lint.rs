@@ -0,0 +1,454 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use miette::{Diagnostic, LabeledSpan, NamedSource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use thiserror::Error;
+
+use crate::common::{
+    config::{
+        condition::command_exists,
+        validation::{locate, Problem},
+        Cell, Command, Pane, Session,
+    },
+    path::{sanitize_path, to_absolute_path},
+};
+
+/// Terminal size the panes of a window have to fit into.
+const TERMINAL: (usize, usize) = (200, 50);
+/// Smallest usable pane, in columns and rows.
+const MIN_PANE: (usize, usize) = (10, 3);
+
+// Commands that are part of the shell rather than found on PATH.
+const BUILTINS: [&str; 16] = [
+    ".", "alias", "bg", "builtin", "cd", "command", "eval", "exec", "exit", "export", "fg", "jobs",
+    "set", "source", "unalias", "unset",
+];
+
+/// A lint rule, configured by its name or code.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Rule {
+    /// L001: session, window or pane paths that do not exist.
+    #[serde(alias = "L001")]
+    MissingPath,
+    /// L002: commands that are not found on PATH.
+    #[serde(alias = "L002")]
+    CommandNotFound,
+    /// L003: windows or panes of a window sharing a name.
+    #[serde(alias = "L003")]
+    DuplicateName,
+    /// L004: panes too small to use in a 200x50 terminal.
+    #[serde(alias = "L004")]
+    PaneTooSmall,
+    /// L005: zoom on a pane with nested panes.
+    #[serde(alias = "L005")]
+    ZoomContainer,
+    /// L006: session names tmux rejects.
+    #[serde(alias = "L006")]
+    SessionName,
+}
+
+/// Severity of a lint rule.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Off,
+    Warning,
+    Error,
+}
+
+impl Rule {
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Rule::MissingPath => "L001",
+            Rule::CommandNotFound => "L002",
+            Rule::DuplicateName => "L003",
+            Rule::PaneTooSmall => "L004",
+            Rule::ZoomContainer => "L005",
+            Rule::SessionName => "L006",
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        match self {
+            Rule::SessionName => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = serde_yaml::to_value(self)
+            .ok()
+            .and_then(|value| value.as_str().map(String::from))
+            .unwrap_or_default();
+        write!(f, "{}({})", self.code(), name)
+    }
+}
+
+/// A problem found by a lint rule.
+#[derive(Debug, Error)]
+#[error("{problem}")]
+pub(crate) struct Finding {
+    pub(crate) rule: Rule,
+    pub(crate) severity: Severity,
+    problem: Problem,
+}
+
+impl Diagnostic for Finding {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.rule))
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(match self.severity {
+            Severity::Error => miette::Severity::Error,
+            _ => miette::Severity::Warning,
+        })
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.problem.help()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.problem.labels()
+    }
+}
+
+/// The findings of linting a config, labeled in its source.
+#[derive(Debug, Error)]
+#[error("Found {} problems in {path:?}", findings.len())]
+pub(crate) struct LintReport {
+    path: PathBuf,
+    source_code: NamedSource<String>,
+    findings: Vec<Finding>,
+}
+
+impl LintReport {
+    /// `source` and `resolved` are the config as loaded by [`Session::from_config_with_source`].
+    pub(crate) fn new(
+        config: &Path,
+        source: String,
+        resolved: &Value,
+        mut findings: Vec<Finding>,
+    ) -> Self {
+        let source_code = locate(
+            config,
+            source,
+            findings.iter_mut().map(|finding| &mut finding.problem),
+            Some(resolved),
+        );
+        Self {
+            path: config.to_path_buf(),
+            source_code,
+            findings,
+        }
+    }
+
+    pub(crate) fn has_errors(&self) -> bool {
+        self.findings
+            .iter()
+            .any(|finding| finding.severity == Severity::Error)
+    }
+}
+
+impl Diagnostic for LintReport {
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(match self.has_errors() {
+            true => miette::Severity::Error,
+            false => miette::Severity::Warning,
+        })
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(&self.source_code)
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        Some(Box::new(
+            self.findings
+                .iter()
+                .map(|finding| finding as &dyn Diagnostic),
+        ))
+    }
+}
+
+impl Session {
+    /// Checks a loaded session for things that validate but misbehave, with the
+    /// severities of `lint` overriding the default ones.
+    pub(crate) fn lint(&self) -> Vec<Finding> {
+        let problems = [
+            (Rule::SessionName, self.lint_session_name()),
+            (Rule::DuplicateName, self.lint_duplicate_names()),
+            (Rule::MissingPath, self.lint_paths()),
+            (Rule::CommandNotFound, self.lint_commands()),
+            (Rule::ZoomContainer, self.lint_zoom()),
+            (Rule::PaneTooSmall, self.lint_pane_sizes()),
+        ];
+        problems
+            .into_iter()
+            .flat_map(|(rule, problems)| {
+                let severity = self.lint.get(&rule).copied().unwrap_or(rule.severity());
+                problems.into_iter().map(move |problem| Finding {
+                    rule,
+                    severity,
+                    problem,
+                })
+            })
+            .filter(|finding| finding.severity != Severity::Off)
+            .collect()
+    }
+
+    fn lint_session_name(&self) -> Vec<Problem> {
+        self.name
+            .contains(['.', ':'])
+            .then(|| {
+                Problem::new(
+                    "name",
+                    format!(
+                        "tmux does not allow '.' or ':' in session name '{}'",
+                        self.name
+                    ),
+                    "not allowed",
+                )
+                .with_help("Use '-' or '_' instead.")
+            })
+            .into_iter()
+            .collect()
+    }
+
+    fn lint_duplicate_names(&self) -> Vec<Problem> {
+        let windows = self
+            .windows
+            .iter()
+            .enumerate()
+            .filter(|(_, window)| window.is_active())
+            .map(|(idx, window)| (format!("windows[{}].name", idx), &window.name));
+        let mut problems = duplicates(windows, "window");
+        for (idx, window) in self.windows.iter().enumerate() {
+            let mut panes = vec![];
+            named_panes(
+                &window.panes,
+                &format!("windows[{}].panes", idx),
+                &mut panes,
+            );
+            problems.extend(duplicates(panes.into_iter(), "pane"));
+        }
+        problems
+    }
+
+    fn lint_paths(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+        let mut check = |path: String, resolved: String| {
+            let exists = to_absolute_path(&resolved).is_ok_and(|path| path.exists());
+            if !exists {
+                problems.push(
+                    Problem::new(
+                        &path,
+                        format!("Path '{}' does not exist", resolved),
+                        "missing",
+                    )
+                    .with_help("tmux falls back to the home directory for missing paths."),
+                );
+            }
+        };
+        check("path".to_string(), self.path.clone());
+        for (idx, window) in self
+            .windows
+            .iter()
+            .enumerate()
+            .filter(|(_, window)| window.is_active())
+        {
+            let path = format!("windows[{}]", idx);
+            let window_path = sanitize_path(&window.path, &self.path);
+            if window.path != "." {
+                check(format!("{}.path", path), window_path.clone());
+            }
+            let mut panes = vec![];
+            pane_paths(&window.panes, &format!("{}.panes", path), &mut panes);
+            for (path, pane_path) in panes {
+                check(path, sanitize_path(pane_path, &window_path));
+            }
+        }
+        problems
+    }
+
+    fn lint_commands(&self) -> Vec<Problem> {
+        let mut commands = vec![];
+        for (path, list, _) in self.command_lists() {
+            flatten_commands(list, &path, &mut commands);
+        }
+        commands
+            .into_iter()
+            .filter(|(_, command)| !command_found(&command.command, &self.path))
+            .map(|(path, command)| {
+                Problem::new(
+                    &format!("{}.command", path),
+                    format!("Command '{}' was not found on PATH", command.command),
+                    "not found",
+                )
+                .with_help("Install it, or set `lint: {command-not-found: off}` for commands that only exist on some machines.")
+            })
+            .collect()
+    }
+
+    fn lint_zoom(&self) -> Vec<Problem> {
+        self.windows
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, window)| {
+                Session::flagged_panes(
+                    &window.panes,
+                    &format!("windows[{}].panes", idx),
+                    "zoom",
+                    |pane| pane.zoom && pane.panes.iter().any(Pane::is_active),
+                )
+            })
+            .map(|path| {
+                Problem::new(&path, "Zoom on a pane with nested panes", "not a leaf")
+                    .with_help("Only panes running commands can be zoomed, move `zoom` to one of the nested panes.")
+            })
+            .collect()
+    }
+
+    fn lint_pane_sizes(&self) -> Vec<Problem> {
+        self.windows
+            .iter()
+            .enumerate()
+            .filter(|(_, window)| window.is_active())
+            .filter_map(|(idx, window)| {
+                let (direction, panes) = match window.layout {
+                    Some(layout) => layout.arrange(&window.active_panes()),
+                    None => window.flex_panes(),
+                };
+                let mut sizes = vec![];
+                match Cell::solve(&panes, &direction, TERMINAL.0, TERMINAL.1) {
+                    Some(cell) => leaf_sizes(&cell, &mut sizes),
+                    // not even a cell per pane
+                    None => sizes.push((0, 0)),
+                }
+                let small: Vec<&(usize, usize)> = sizes
+                    .iter()
+                    .filter(|(width, height)| *width < MIN_PANE.0 || *height < MIN_PANE.1)
+                    .collect();
+                let smallest = small.iter().min_by_key(|(width, height)| width * height)?;
+                Some(
+                    Problem::new(
+                        &format!("windows[{}]", idx),
+                        format!(
+                            "Window '{}' has {} panes smaller than {}x{} in a {}x{} terminal, the smallest is {}x{}",
+                            window.name,
+                            small.len(),
+                            MIN_PANE.0,
+                            MIN_PANE.1,
+                            TERMINAL.0,
+                            TERMINAL.1,
+                            smallest.0,
+                            smallest.1
+                        ),
+                        "too small",
+                    )
+                    .with_help("Use fewer or less deeply nested panes, or spread them over more windows."),
+                )
+            })
+            .collect()
+    }
+}
+
+fn duplicates<'a>(names: impl Iterator<Item = (String, &'a String)>, kind: &str) -> Vec<Problem> {
+    let mut seen: HashMap<&String, Vec<String>> = HashMap::new();
+    let mut order = vec![];
+    for (path, name) in names {
+        let paths = seen.entry(name).or_default();
+        if paths.is_empty() {
+            order.push(name);
+        }
+        paths.push(path);
+    }
+    order
+        .into_iter()
+        .filter_map(|name| {
+            let paths = seen.remove(name)?;
+            (paths.len() > 1).then(|| {
+                Problem::new(
+                    &paths[1],
+                    format!("The {} name '{}' is used {} times", kind, name, paths.len()),
+                    "duplicate",
+                )
+                .at(paths)
+                .with_help(format!(
+                    "tmux targets {}s by name, give each one its own name.",
+                    kind
+                ))
+            })
+        })
+        .collect()
+}
+
+fn named_panes<'a>(panes: &'a [Pane], path: &str, names: &mut Vec<(String, &'a String)>) {
+    for (idx, pane) in panes.iter().enumerate().filter(|(_, p)| p.is_active()) {
+        let path = format!("{}[{}]", path, idx);
+        if let Some(name) = &pane.name {
+            names.push((format!("{}.name", path), name));
+        }
+        named_panes(&pane.panes, &format!("{}.panes", path), names);
+    }
+}
+
+fn pane_paths<'a>(panes: &'a [Pane], path: &str, paths: &mut Vec<(String, &'a String)>) {
+    for (idx, pane) in panes.iter().enumerate().filter(|(_, p)| p.is_active()) {
+        let path = format!("{}[{}]", path, idx);
+        if pane.path != "." {
+            paths.push((format!("{}.path", path), &pane.path));
+        }
+        pane_paths(&pane.panes, &format!("{}.panes", path), paths);
+    }
+}
+
+fn flatten_commands<'a>(
+    commands: &'a [Command],
+    path: &str,
+    flattened: &mut Vec<(String, &'a Command)>,
+) {
+    for (idx, command) in commands.iter().enumerate() {
+        let path = format!("{}[{}]", path, idx);
+        if command.parallel.is_empty() {
+            flattened.push((path, command));
+        } else {
+            flatten_commands(&command.parallel, &format!("{}.parallel", path), flattened);
+        }
+    }
+}
+
+// Commands using variables or shell syntax can only be checked by running them.
+fn command_found(command: &str, session_path: &String) -> bool {
+    if command.is_empty()
+        || command.contains(['$', '`', '(', '=', '|', '&', ';'])
+        || BUILTINS.contains(&command)
+    {
+        return true;
+    }
+    if command.contains('/') {
+        return to_absolute_path(&sanitize_path(&command.to_string(), session_path))
+            .is_ok_and(|path| command_exists(&path.to_string_lossy()));
+    }
+    command_exists(command)
+}
+
+// Sizes of the leaf panes as tmux lays them out.
+fn leaf_sizes(cell: &Cell, sizes: &mut Vec<(usize, usize)>) {
+    if cell.children.is_empty() {
+        sizes.push((cell.rect.width, cell.rect.height));
+    }
+    for child in &cell.children {
+        leaf_sizes(child, sizes);
+    }
+}
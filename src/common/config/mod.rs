@@ -1,9 +1,27 @@
+mod condition;
+pub(crate) mod extends;
+pub(crate) mod format;
+mod include;
+mod layout;
+pub(crate) mod lint;
 mod model;
+pub(crate) mod normalize;
+mod size;
 pub(crate) mod util;
 mod validation;
+mod variables;
+pub(crate) mod version;
+mod wait;
 
+pub(crate) use condition::Condition;
+pub(crate) use layout::{Cell, Grid, Layout, Rect, Span};
 pub(crate) use model::Command;
 pub(crate) use model::FlexDirection;
 pub(crate) use model::Pane;
 pub(crate) use model::Session;
 pub(crate) use model::Window;
+pub(crate) use size::Size;
+pub(crate) use wait::WaitFor;
+
+#[cfg(test)]
+mod test;
@@ -1,15 +1,33 @@
-use miette::{bail, IntoDiagnostic, Result};
+use miette::{Context, IntoDiagnostic, Result};
+use regex::Regex;
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{
+        ArrayValidation, InstanceType, Metadata, ObjectValidation, Schema, SchemaObject,
+        SubschemaValidation,
+    },
+    schema_for, JsonSchema,
+};
 use serde::{Deserialize, Serialize};
 use serde_valid::Validate;
+use serde_yaml::Value;
 use std::{collections::HashMap, fmt::Display, fs::read_to_string, path::Path};
 
-use crate::common::{config::validation::generate_report, path::to_absolute_path};
-use serde_valid::{
-    yaml::FromYamlStr,
-    Error::{DeserializeError, ValidationError},
+use crate::common::{
+    config::{
+        extends::load_config,
+        include::INCLUDE,
+        lint::{Rule, Severity},
+        validation::{deserialize_problem, validation_problems, ConfigError, Problem},
+        variables::unresolved_problems,
+        version::VERSION,
+        Condition, Grid, Layout, Size, Span, WaitFor,
+    },
+    path::to_absolute_path,
 };
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
+/// Direction child panes are laid out in.
 pub enum FlexDirection {
     #[serde(rename = "row")]
     #[default]
@@ -24,12 +42,72 @@ impl FlexDirection {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Validate, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, Validate, PartialEq, JsonSchema)]
+/// A command and its arguments.
 pub(crate) struct Command {
+    /// Executable to run.
     #[serde(default)]
     pub(crate) command: String,
+    /// Arguments passed to the command.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub(crate) args: Vec<String>,
+    /// Only run the command once the gate is ready.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) wait_for: Option<WaitFor>,
+    /// Press enter after typing the command into a pane, `false` only types it.
+    #[serde(default = "enter", skip_serializing_if = "is_enter")]
+    pub(crate) enter: bool,
+    /// Milliseconds to wait before running the next command.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub(crate) delay_ms: u64,
+    /// Send the command to a pane as literal text, without tmux looking up key names like `Enter`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) literal: bool,
+    /// Seconds after which a startup or shutdown command is killed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) timeout: Option<u64>,
+    /// How often a failed startup or shutdown command is retried.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub(crate) retries: u32,
+    /// Keep going when a startup or shutdown command fails.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) allow_failure: bool,
+    /// Directory to run a startup or shutdown command in, relative to the session or window path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) cwd: Option<String>,
+    /// Commands to run at the same time instead of `command`, the next step starts once all of them succeeded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) parallel: Vec<Command>,
+}
+
+fn enter() -> bool {
+    true
+}
+
+fn is_enter(value: &bool) -> bool {
+    *value
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Self {
+            command: String::new(),
+            args: vec![],
+            wait_for: None,
+            enter: enter(),
+            delay_ms: 0,
+            literal: false,
+            timeout: None,
+            retries: 0,
+            allow_failure: false,
+            cwd: None,
+            parallel: vec![],
+        }
+    }
 }
 
 impl Command {
@@ -37,7 +115,11 @@ impl Command {
         let mut parts = input.split_whitespace();
         let command = parts.next().unwrap_or_default().to_string();
         let args = parts.map(|s| s.to_string()).collect();
-        Command { command, args }
+        Command {
+            command,
+            args,
+            ..Default::default()
+        }
     }
 }
 
@@ -65,33 +147,82 @@ impl Display for Command {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Validate)]
+#[derive(Debug, Deserialize, Serialize, Clone, Validate, JsonSchema)]
+/// A pane, either a leaf running commands or a container of nested panes.
 pub(crate) struct Pane {
+    /// Direction the nested panes are laid out in.
     #[serde(default, skip_serializing_if = "FlexDirection::is_default")]
     pub(crate) flex_direction: FlexDirection,
-    #[validate(minimum = 1, message = "Flex has to be >= 0")]
-    #[serde(default = "flex")]
+    /// Share of the parent's space relative to the sibling panes.
+    #[validate(minimum = 1, message = "Flex has to be >= 1")]
+    #[schemars(range(min = 1))]
+    #[serde(default = "flex", skip_serializing_if = "is_default_flex")]
     pub(crate) flex: usize,
+    /// Fixed size along the parent's split direction, in cells or percent of the parent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) size: Option<Size>,
+    /// Lower bound of the size resolved from flex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) min_size: Option<Size>,
+    /// Upper bound of the size resolved from flex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_size: Option<Size>,
+    /// Name of the pane.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub(crate) name: Option<String>,
+    /// Working directory, relative to the session path or absolute.
     #[serde(default = "default_path", skip_serializing_if = "if_is_default_path")]
     pub(crate) path: String,
+    /// Tmux style applied to the pane.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub(crate) style: Option<String>,
+    /// Commands run in the pane once it is created.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub(crate) commands: Vec<Command>,
+    /// Environment variables set for the pane and its nested panes.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub(crate) env: HashMap<String, String>,
+    /// Nested panes.
+    #[validate]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "panes")]
     pub(crate) panes: Vec<Pane>,
+    /// Zoom the pane once the session is started, at most one per window.
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub(crate) zoom: bool,
+    /// Focus the pane once the session is started, at most one per window.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) focus: bool,
+    /// Only start the pane commands once the gate is ready.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) wait_for: Option<WaitFor>,
+    /// Rows and columns the pane takes up in the grid of its window.
+    #[validate]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) span: Option<Span>,
+    /// Shell the pane runs instead of the default one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) shell: Option<String>,
+    /// Run the commands as the pane process instead of typing them into a shell.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) exec: bool,
+    /// Only create the pane if the condition matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) when: Option<Condition>,
 }
 
 fn flex() -> usize {
     1
 }
 
+fn is_default_flex(value: &usize) -> bool {
+    *value == flex()
+}
+
+fn version() -> u32 {
+    VERSION
+}
+
 fn default_path() -> String {
     ".".to_string()
 }
@@ -100,87 +231,328 @@ fn if_is_default_path(value: &str) -> bool {
     value == default_path()
 }
 
-#[derive(Debug, Deserialize, Serialize, Validate)]
+// Window entries may also be `include:` references, which are spliced in before deserialization.
+fn windows_schema(gen: &mut SchemaGenerator) -> Schema {
+    let include = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(ObjectValidation {
+            required: [INCLUDE.to_string()].into(),
+            properties: [(
+                INCLUDE.to_string(),
+                SchemaObject {
+                    instance_type: Some(InstanceType::String.into()),
+                    metadata: Some(Box::new(Metadata {
+                        description: Some(
+                            "Path to a window fragment, relative to the config.".to_string(),
+                        ),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                }
+                .into(),
+            )]
+            .into(),
+            additional_properties: Some(Box::new(Schema::Bool(false))),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+
+    SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(
+                Schema::Object(SchemaObject {
+                    subschemas: Some(Box::new(SubschemaValidation {
+                        any_of: Some(vec![gen.subschema_for::<Window>(), include.into()]),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                })
+                .into(),
+            ),
+            min_items: Some(1),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate, JsonSchema)]
+/// A window, or tab in zellij.
 pub(crate) struct Window {
+    /// Name of the window.
     #[validate(
         min_length = 3,
         message = "Window names should have at least 3 characters."
     )]
+    #[schemars(length(min = 3))]
     pub(crate) name: String,
+    /// Direction the panes are laid out in.
     #[serde(default, skip_serializing_if = "FlexDirection::is_default")]
     pub(crate) flex_direction: FlexDirection,
+    /// Layout preset for a flat list of panes, instead of a flex layout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) layout: Option<Layout>,
+    /// Grid a flat list of panes is placed in row by row, instead of a flex layout.
+    #[validate]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) grid: Option<Grid>,
+    /// Working directory of the panes, relative to the session path or absolute.
+    #[serde(default = "default_path", skip_serializing_if = "if_is_default_path")]
+    pub(crate) path: String,
+    /// Environment variables set for all panes of the window.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub(crate) env: HashMap<String, String>,
+    /// Commands run in every pane before its own commands.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) pane_commands: Vec<Command>,
+    /// Commands run in the window path before the window is created.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) startup: Vec<Command>,
+    /// Commands run in the window path before the session is stopped.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) shutdown: Vec<Command>,
+    /// Panes of the window.
     #[validate]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    // serde_valid takes string values of serde attributes as field name
+    #[serde(rename = "panes")]
     pub(crate) panes: Vec<Pane>,
+    /// Focus the window once the session is started, at most one per session.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) focus: bool,
+    /// Only create the window if the condition matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) when: Option<Condition>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Validate)]
+#[derive(Debug, Deserialize, Serialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+/// A laio session configuration.
 pub(crate) struct Session {
+    /// Version of the config format, older configs can be updated with `laio config migrate`.
+    #[serde(default = "version")]
+    pub(crate) version: u32,
+    /// Name of the session.
     #[validate(
         min_length = 3,
         message = "The session name should have at least 3 characters."
     )]
+    #[schemars(length(min = 3))]
     pub(crate) name: String,
+    /// Config name or path this config extends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) extends: Option<String>,
+    /// Root path of the session, relative paths are resolved against the config location.
     #[serde(default = "default_path")]
     pub(crate) path: String,
-    #[serde(default, alias = "commands", skip_serializing_if = "Vec::is_empty")]
+    /// Commands run in the session path before the session is created.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub(crate) startup: Vec<Command>,
+    /// Commands run in the session path when the session is stopped, before its panes are closed.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub(crate) shutdown: Vec<Command>,
+    /// Commands run before laio talks to the multiplexer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) pre_start: Vec<Command>,
+    /// Commands run once all windows are created and their commands are sent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) post_start: Vec<Command>,
+    /// Commands run whenever a client attaches to the session (tmux only).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) on_attach: Vec<Command>,
+    /// Commands run whenever a client detaches from the session (tmux only).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) on_detach: Vec<Command>,
+    /// Environment variables set for the session.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub(crate) env: HashMap<String, String>,
+    /// Variables available for `${name}` interpolation.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub(crate) vars: HashMap<String, String>,
+    /// Severities of `laio config lint` rules by name or code, e.g. `command-not-found: off`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub(crate) lint: HashMap<Rule, Severity>,
+    /// Windows of the session, or `include` entries referencing window fragments.
     #[validate]
     #[validate(min_items = 1, message = "At least one window is required.")]
+    #[schemars(schema_with = "windows_schema")]
     pub(crate) windows: Vec<Window>,
 }
 
 impl Pane {
+    /// A pane that only holds nested panes.
+    pub(crate) fn container(flex_direction: FlexDirection, flex: usize, panes: Vec<Pane>) -> Self {
+        Pane {
+            flex_direction,
+            flex,
+            size: None,
+            min_size: None,
+            max_size: None,
+            name: None,
+            path: default_path(),
+            style: None,
+            commands: vec![],
+            env: HashMap::new(),
+            panes,
+            zoom: false,
+            focus: false,
+            wait_for: None,
+            span: None,
+            shell: None,
+            exec: false,
+            when: None,
+        }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.when.as_ref().is_none_or(Condition::matches)
+    }
+
+    pub(crate) fn active_panes(&self) -> Vec<Pane> {
+        active_panes(&self.panes)
+    }
+
     pub(crate) fn first_leaf_path(&self) -> Option<&String> {
-        if self.panes.is_empty() {
+        if !self.panes.iter().any(Pane::is_active) {
             return Some(&self.path);
         }
-        for pane in &self.panes {
+        for pane in self.panes.iter().filter(|p| p.is_active()) {
             if let Some(path) = pane.first_leaf_path() {
                 return Some(path);
             }
         }
         None
     }
+
+    pub(crate) fn first_leaf_env(&self) -> HashMap<String, String> {
+        let mut env = self.env.clone();
+        if let Some(pane) = self.panes.iter().find(|p| p.is_active()) {
+            env.extend(pane.first_leaf_env());
+        }
+        env
+    }
+
+    fn resolve_conditions(&mut self, session_path: &String) {
+        if let Some(when) = self.when.as_mut() {
+            when.resolve_paths(session_path);
+        }
+        for pane in self.panes.iter_mut() {
+            pane.resolve_conditions(session_path);
+        }
+    }
 }
 
 impl Window {
+    pub(crate) fn is_active(&self) -> bool {
+        self.when.as_ref().is_none_or(Condition::matches)
+    }
+
+    pub(crate) fn active_panes(&self) -> Vec<Pane> {
+        active_panes(&self.panes)
+    }
+
+    /// Direction and panes of the flex layout, with the grid compiled into nested panes.
+    pub(crate) fn flex_panes(&self) -> (FlexDirection, Vec<Pane>) {
+        let panes = self.active_panes();
+        match self.grid.map(|grid| grid.arrange(&panes)) {
+            Some(Ok(arranged)) => arranged,
+            Some(Err(e)) => {
+                log::warn!("{}, laying out the panes of '{}' in a row", e, self.name);
+                (FlexDirection::Row, panes)
+            }
+            None => (self.flex_direction.clone(), panes),
+        }
+    }
+
     pub(crate) fn first_leaf_path(&self) -> Option<&String> {
-        for pane in &self.panes {
+        for pane in self.panes.iter().filter(|p| p.is_active()) {
             if let Some(path) = pane.first_leaf_path() {
                 return Some(path);
             }
         }
         None
     }
+
+    pub(crate) fn first_leaf_env(&self) -> HashMap<String, String> {
+        let mut env = self.env.clone();
+        if let Some(pane) = self.panes.iter().find(|p| p.is_active()) {
+            env.extend(pane.first_leaf_env());
+        }
+        env
+    }
+
+    fn resolve_conditions(&mut self, session_path: &String) {
+        if let Some(when) = self.when.as_mut() {
+            when.resolve_paths(session_path);
+        }
+        for pane in self.panes.iter_mut() {
+            pane.resolve_conditions(session_path);
+        }
+    }
+}
+
+fn active_panes(panes: &[Pane]) -> Vec<Pane> {
+    panes.iter().filter(|p| p.is_active()).cloned().collect()
 }
 
 impl Session {
-    pub(crate) fn from_config(config: &Path) -> Result<Session> {
-        let session_config = read_to_string(config).into_diagnostic()?;
-        let mut session: Session =
-            Session::from_yaml_str(&session_config).map_err(|e| -> miette::Report {
-                match e {
-                    DeserializeError(_) => miette::Report::msg(format!(
-                        "Failed to parse config: {:?}\n\n{}",
-                        &config, e
-                    )),
-                    ValidationError(_) => {
-                        let validation_errors = e.as_validation_errors();
-                        let error_tree = generate_report(validation_errors); // Converts the error tree into a Report
-                        miette::Report::msg(format!(
-                            "Failed to parse config: {:?}\n\n{}",
-                            &config, error_tree
-                        ))
-                    }
-                }
-            })?;
+    pub(crate) fn active_windows(&self) -> Vec<&Window> {
+        self.windows.iter().filter(|w| w.is_active()).collect()
+    }
 
-        session.validate_zoom()?;
+    /// Loads the session of a config, `config_dir` holds the configs it may extend by name.
+    pub(crate) fn from_config(
+        config: &Path,
+        config_dir: &Path,
+        vars: &HashMap<String, String>,
+    ) -> Result<Session> {
+        Session::from_config_with_source(config, config_dir, vars).map(|(session, _, _)| session)
+    }
+
+    /// Loads a session like [`Session::from_config`], also returning the source
+    /// of the config and the value it resolved to, e.g. to label lint findings.
+    pub(crate) fn from_config_with_source(
+        config: &Path,
+        config_dir: &Path,
+        vars: &HashMap<String, String>,
+    ) -> Result<(Session, String, Value)> {
+        let session_config = load_config(config, config_dir)?;
+        let source = read_to_string(config)
+            .into_diagnostic()
+            .wrap_err(format!("Could not read config {:?}", config))?;
+        let config_error = |problems: Vec<Problem>| {
+            ConfigError::new(
+                "config",
+                config,
+                source.clone(),
+                problems,
+                Some(&session_config),
+            )
+        };
+
+        let mut session: Session = match serde_path_to_error::deserialize(session_config.clone()) {
+            Ok(session) => session,
+            Err(e) => return Err(config_error(vec![deserialize_problem(&e)]).into()),
+        };
+
+        // the resolved values have to pass validation too
+        let unresolved = session.interpolate(vars);
+        let mut problems = unresolved_problems(&session_config, &unresolved);
+        if let Err(errors) = session.validate() {
+            problems.extend(validation_problems(&errors, ""));
+        }
+        problems.extend(session.validate_zoom());
+        problems.extend(session.validate_focus());
+        problems.extend(session.validate_exec());
+        problems.extend(session.validate_layout());
+        problems.extend(session.validate_wait_for());
+        problems.extend(session.validate_parallel());
+        if !problems.is_empty() {
+            return Err(config_error(problems).into());
+        }
 
         let session_path = if session.path.starts_with('.') {
             let parent = config
@@ -195,40 +567,435 @@ impl Session {
         };
 
         session.path = session_path.to_string_lossy().to_string();
+        for window in session.windows.iter_mut() {
+            window.resolve_conditions(&session.path);
+        }
 
         log::debug!("Final session path: {}", session.path);
-        Ok(session)
+        Ok((session, source, session_config))
     }
 
-    fn validate_pane_zoom(panes: &[Pane], window_name: &str) -> Result<u32> {
-        let mut zoom_count = 0;
-        for pane in panes {
-            if pane.zoom {
-                zoom_count += 1;
-            }
-            zoom_count += Session::validate_pane_zoom(&pane.panes.clone(), window_name)?;
+    pub(crate) fn json_schema() -> Result<String> {
+        serde_json::to_string_pretty(&schema_for!(Session))
+            .into_diagnostic()
+            .wrap_err("Unable to generate json schema.")
+    }
 
-            if zoom_count > 1 {
-                bail!(
-                    "Window '{}', has more than one pane with zoom enabled",
-                    window_name
+    // Paths of the `field` flags of all panes in the tree that have it enabled.
+    pub(crate) fn flagged_panes(
+        panes: &[Pane],
+        path: &str,
+        field: &str,
+        flag: fn(&Pane) -> bool,
+    ) -> Vec<String> {
+        panes
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, pane)| {
+                let path = format!("{}[{}]", path, idx);
+                flag(pane)
+                    .then(|| format!("{}.{}", path, field))
+                    .into_iter()
+                    .chain(Session::flagged_panes(
+                        &pane.panes,
+                        &format!("{}.panes", path),
+                        field,
+                        flag,
+                    ))
+            })
+            .collect()
+    }
+
+    fn validate_zoom(&self) -> Vec<Problem> {
+        self.windows
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, window)| {
+                let path = format!("windows[{}]", idx);
+                let zoomed = Session::flagged_panes(
+                    &window.panes,
+                    &format!("{}.panes", path),
+                    "zoom",
+                    |pane| pane.zoom,
                 );
+                (zoomed.len() > 1).then(|| {
+                    Problem::new(
+                        &path,
+                        format!(
+                            "Window '{}' has more than one pane with zoom enabled",
+                            window.name
+                        ),
+                        "zoomed",
+                    )
+                    .at(zoomed)
+                    .with_help("Only one pane per window can have `zoom: true`.")
+                })
+            })
+            .collect()
+    }
+
+    fn validate_layout(&self) -> Vec<Problem> {
+        self.windows
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, window)| {
+                let path = format!("windows[{}]", idx);
+                let mut problems = vec![];
+                if window.layout.is_some() && window.grid.is_some() {
+                    problems.push(
+                        Problem::new(
+                            &format!("{}.grid", path),
+                            format!("Window '{}' has both a layout and a grid", window.name),
+                            "grid",
+                        )
+                        .with_help("Use either a `layout` preset or a `grid`."),
+                    );
+                }
+                let arrangement = match (window.layout, window.grid) {
+                    (Some(layout), _) => format!("the {} layout", layout),
+                    (None, Some(grid)) => format!("a {}x{} grid", grid.rows, grid.columns),
+                    (None, None) => return problems,
+                };
+                let field = if window.layout.is_some() { "layout" } else { "grid" };
+                let nested: Vec<String> = window
+                    .panes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pane)| !pane.panes.is_empty())
+                    .map(|(pdx, _)| format!("{}.panes[{}].panes", path, pdx))
+                    .collect();
+                if !nested.is_empty() {
+                    problems.push(
+                        Problem::new(
+                            &format!("{}.{}", path, field),
+                            format!(
+                                "Window '{}' uses {} but has nested panes",
+                                window.name, arrangement
+                            ),
+                            field,
+                        )
+                        .at(nested)
+                        .with_help(format!(
+                            "A {} arranges a flat list of panes, remove `{}` to nest panes.",
+                            field, field
+                        )),
+                    );
+                } else if let (None, Some(grid)) = (window.layout, window.grid) {
+                    if let Err(e) = grid.arrange(&window.panes) {
+                        problems.push(
+                            Problem::new(&format!("{}.grid", path), e, "grid").with_help(
+                                "Grow the grid or change the spans, so that each pane fits and rows or columns can be split without cutting through a pane.",
+                            ),
+                        );
+                    }
+                }
+                problems
+            })
+            .collect()
+    }
+
+    fn validate_exec(&self) -> Vec<Problem> {
+        self.windows
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, window)| {
+                let path = format!("windows[{}]", idx);
+                let nested = Session::flagged_panes(
+                    &window.panes,
+                    &format!("{}.panes", path),
+                    "exec",
+                    |pane| pane.exec && !pane.panes.is_empty(),
+                );
+                let empty = if window.pane_commands.is_empty() {
+                    Session::flagged_panes(
+                        &window.panes,
+                        &format!("{}.panes", path),
+                        "exec",
+                        |pane| pane.exec && pane.panes.is_empty() && pane.commands.is_empty(),
+                    )
+                } else {
+                    vec![]
+                };
+                let nested_problem = (!nested.is_empty()).then(|| {
+                    Problem::new(
+                        &path,
+                        format!(
+                            "Window '{}' has panes with nested panes that exec their commands",
+                            window.name
+                        ),
+                        "nested",
+                    )
+                    .at(nested)
+                    .with_help("Only panes without nested panes can have `exec: true`.")
+                });
+                let empty_problem = (!empty.is_empty()).then(|| {
+                    Problem::new(
+                        &path,
+                        format!(
+                            "Window '{}' has panes with no commands to exec",
+                            window.name
+                        ),
+                        "no commands",
+                    )
+                    .at(empty)
+                    .with_help("Add `commands` to the pane or remove `exec: true`.")
+                });
+                nested_problem.into_iter().chain(empty_problem)
+            })
+            .collect()
+    }
+
+    fn validate_focus(&self) -> Vec<Problem> {
+        let focused: Vec<String> = self
+            .windows
+            .iter()
+            .enumerate()
+            .filter(|(_, window)| window.focus)
+            .map(|(idx, _)| format!("windows[{}].focus", idx))
+            .collect();
+
+        let session_problem = (focused.len() > 1).then(|| {
+            Problem::new(
+                "windows",
+                format!(
+                    "Session '{}' has more than one window with focus enabled",
+                    self.name
+                ),
+                "focused",
+            )
+            .at(focused)
+            .with_help("Only one window per session can have `focus: true`.")
+        });
+
+        let window_problems = self.windows.iter().enumerate().filter_map(|(idx, window)| {
+            let path = format!("windows[{}]", idx);
+            let focused = Session::flagged_panes(
+                &window.panes,
+                &format!("{}.panes", path),
+                "focus",
+                |pane| pane.focus,
+            );
+            (focused.len() > 1).then(|| {
+                Problem::new(
+                    &path,
+                    format!(
+                        "Window '{}' has more than one pane with focus enabled",
+                        window.name
+                    ),
+                    "focused",
+                )
+                .at(focused)
+                .with_help("Only one pane per window can have `focus: true`.")
+            })
+        });
+
+        session_problem.into_iter().chain(window_problems).collect()
+    }
+
+    /// All command lists of the session, with their path in the config and whether they run in a pane.
+    pub(crate) fn command_lists(&self) -> Vec<(String, &[Command], bool)> {
+        let mut lists: Vec<(String, &[Command], bool)> = [
+            ("startup", &self.startup),
+            ("shutdown", &self.shutdown),
+            ("pre_start", &self.pre_start),
+            ("post_start", &self.post_start),
+            ("on_attach", &self.on_attach),
+            ("on_detach", &self.on_detach),
+        ]
+        .into_iter()
+        .map(|(field, commands)| (field.to_string(), commands.as_slice(), false))
+        .collect();
+        for (idx, window) in self.windows.iter().enumerate() {
+            let path = format!("windows[{}]", idx);
+            lists.push((format!("{}.startup", path), &window.startup, false));
+            lists.push((format!("{}.shutdown", path), &window.shutdown, false));
+            lists.push((
+                format!("{}.pane_commands", path),
+                &window.pane_commands,
+                true,
+            ));
+            pane_command_lists(&window.panes, &format!("{}.panes", path), &mut lists);
+        }
+        lists
+    }
+
+    fn validate_parallel(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+        for (path, commands, in_pane) in self.command_lists() {
+            for (idx, command) in commands.iter().enumerate() {
+                if command.parallel.is_empty() {
+                    continue;
+                }
+                let path = format!("{}[{}]", path, idx);
+                if in_pane {
+                    problems.push(
+                        Problem::new(
+                            &format!("{}.parallel", path),
+                            "parallel groups are only supported in startup, shutdown and hooks",
+                            "in a pane",
+                        )
+                        .with_help("Pane commands are typed into the pane one after another."),
+                    );
+                }
+                if !command.command.is_empty() {
+                    problems.push(
+                        Problem::new(
+                            &path,
+                            "A command either runs a `command` or a `parallel` group",
+                            "both",
+                        )
+                        .with_help("Move the command into the group or into a step of its own."),
+                    );
+                }
+                for (jdx, nested) in command.parallel.iter().enumerate() {
+                    if !nested.parallel.is_empty() {
+                        problems.push(
+                            Problem::new(
+                                &format!("{}.parallel[{}].parallel", path, jdx),
+                                "parallel groups can not be nested",
+                                "nested",
+                            )
+                            .with_help("Merge the nested group into the outer one."),
+                        );
+                    }
+                }
             }
         }
-        Ok(zoom_count)
+        problems
     }
 
-    fn validate_zoom(&self) -> Result<()> {
+    fn validate_wait_for(&self) -> Vec<Problem> {
+        let mut gates = vec![];
+        for (field, commands) in [
+            ("startup", &self.startup),
+            ("shutdown", &self.shutdown),
+            ("pre_start", &self.pre_start),
+            ("post_start", &self.post_start),
+            ("on_attach", &self.on_attach),
+            ("on_detach", &self.on_detach),
+        ] {
+            command_gates(commands, field, false, &mut gates);
+        }
+        for (idx, window) in self.windows.iter().enumerate() {
+            let path = format!("windows[{}]", idx);
+            command_gates(
+                &window.startup,
+                &format!("{}.startup", path),
+                false,
+                &mut gates,
+            );
+            command_gates(
+                &window.shutdown,
+                &format!("{}.shutdown", path),
+                false,
+                &mut gates,
+            );
+            command_gates(
+                &window.pane_commands,
+                &format!("{}.pane_commands", path),
+                true,
+                &mut gates,
+            );
+            pane_gates(&window.panes, &format!("{}.panes", path), &mut gates);
+        }
+
+        let mut names = vec![];
         for window in &self.windows {
-            let zoom_count = Session::validate_pane_zoom(&window.panes, &window.name)?;
-            if zoom_count > 1 {
-                bail!(
-                    "Window '{}' has more than one pane with zoom enabled",
-                    window.name
-                );
-            }
+            pane_names(&window.panes, &mut names);
         }
 
-        Ok(())
+        gates
+            .into_iter()
+            .filter_map(|(path, wait_for, in_pane)| {
+                let problem = |message: String, label: &str, help: String| {
+                    Some(Problem::new(&path, message, label).with_help(help))
+                };
+                match (&wait_for.pane, &wait_for.pattern) {
+                    (None, None) if wait_for.port.is_none() && wait_for.file.is_none() => problem(
+                        "wait_for has nothing to wait for".to_string(),
+                        "empty",
+                        "Set a `port`, a `file` or a `pane` and `pattern`.".to_string(),
+                    ),
+                    (Some(_), None) | (None, Some(_)) => problem(
+                        "wait_for needs both pane and pattern to match pane output".to_string(),
+                        "incomplete",
+                        "Set the `name` of the pane and the `pattern` to find in its output."
+                            .to_string(),
+                    ),
+                    (Some(pane), Some(_)) if !in_pane => problem(
+                        format!("Unable to wait for the output of pane '{}' here", pane),
+                        "not in a pane",
+                        "Waiting for pane output is only supported for panes and their commands."
+                            .to_string(),
+                    ),
+                    (Some(pane), Some(_)) if !names.contains(&pane.as_str()) => problem(
+                        format!("wait_for references unknown pane '{}'", pane),
+                        "unknown pane",
+                        format!("Give the pane to wait for a name, e.g. `name: {}`.", pane),
+                    ),
+                    (_, Some(pattern)) => Regex::new(pattern).err().and_then(|e| {
+                        problem(
+                            format!("Invalid wait_for pattern: {}", e),
+                            "invalid pattern",
+                            "Patterns are regular expressions, e.g. `listening on .*:8080`."
+                                .to_string(),
+                        )
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+fn pane_command_lists<'a>(
+    panes: &'a [Pane],
+    path: &str,
+    lists: &mut Vec<(String, &'a [Command], bool)>,
+) {
+    for (idx, pane) in panes.iter().enumerate() {
+        let path = format!("{}[{}]", path, idx);
+        lists.push((format!("{}.commands", path), &pane.commands, true));
+        pane_command_lists(&pane.panes, &format!("{}.panes", path), lists);
+    }
+}
+
+fn command_gates<'a>(
+    commands: &'a [Command],
+    path: &str,
+    in_pane: bool,
+    gates: &mut Vec<(String, &'a WaitFor, bool)>,
+) {
+    for (idx, command) in commands.iter().enumerate() {
+        let path = format!("{}[{}]", path, idx);
+        if let Some(wait_for) = &command.wait_for {
+            gates.push((format!("{}.wait_for", path), wait_for, in_pane));
+        }
+        command_gates(
+            &command.parallel,
+            &format!("{}.parallel", path),
+            in_pane,
+            gates,
+        );
+    }
+}
+
+fn pane_gates<'a>(panes: &'a [Pane], path: &str, gates: &mut Vec<(String, &'a WaitFor, bool)>) {
+    for (idx, pane) in panes.iter().enumerate() {
+        let path = format!("{}[{}]", path, idx);
+        if let Some(wait_for) = &pane.wait_for {
+            gates.push((format!("{}.wait_for", path), wait_for, true));
+        }
+        command_gates(&pane.commands, &format!("{}.commands", path), true, gates);
+        pane_gates(&pane.panes, &format!("{}.panes", path), gates);
+    }
+}
+
+fn pane_names<'a>(panes: &'a [Pane], names: &mut Vec<&'a str>) {
+    for pane in panes {
+        if let Some(name) = &pane.name {
+            names.push(name);
+        }
+        pane_names(&pane.panes, names);
     }
 }
@@ -0,0 +1,286 @@
+use std::{mem::take, path::Path};
+
+use miette::{bail, IntoDiagnostic, Result};
+use serde_yaml::{Mapping, Value};
+
+use crate::common::config::{
+    format::Format,
+    include::INCLUDE,
+    validation::{deserialize_problem, ConfigError},
+    version::migrate,
+    Session,
+};
+
+// Maps that are serialized in random order unless sorted.
+const MAPS: [&str; 3] = ["env", "lint", "vars"];
+
+/// Rewrites a config in its canonical form: fields in model order, defaults left
+/// out and maps sorted. Comments and blank lines are kept with the line they
+/// belong to, as long as that line is still there.
+pub(crate) fn normalize(config: &Path, content: &str) -> Result<String> {
+    let format = Format::from_path(config);
+    let mut value = format.parse(content).map_err(|problem| {
+        ConfigError::new("config", config, content.to_string(), vec![*problem], None)
+    })?;
+
+    // configs extending another one only hold what they change
+    let keys: Vec<Value> = match value.as_mapping() {
+        Some(mapping) => mapping.keys().cloned().collect(),
+        None => bail!("{:?} is not a laio config.", config),
+    };
+    let deprecations = migrate(&mut value)?;
+    if !deprecations.is_empty() {
+        bail!(
+            "{:?} uses deprecated fields, run `laio config migrate` first: {}",
+            config,
+            deprecations.join(", ")
+        );
+    }
+    let Some(mapping) = value.as_mapping_mut() else {
+        bail!("{:?} is not a laio config.", config);
+    };
+    let includes = take_includes(mapping);
+    mapping.entry("name".into()).or_insert("".into());
+    mapping
+        .entry("windows".into())
+        .or_insert(Value::Sequence(vec![]));
+
+    let session: Session = serde_path_to_error::deserialize(value.clone()).map_err(|e| {
+        ConfigError::new(
+            "config",
+            config,
+            content.to_string(),
+            vec![deserialize_problem(&e)],
+            Some(&value),
+        )
+    })?;
+
+    let mut normalized = serde_yaml::to_value(&session).into_diagnostic()?;
+    if let Some(mapping) = normalized.as_mapping_mut() {
+        mapping.retain(|key, _| keys.contains(key));
+        if let Some(Value::Sequence(windows)) = mapping.get_mut("windows") {
+            for (idx, include) in includes {
+                windows.insert(idx.min(windows.len()), include);
+            }
+        }
+    }
+    sort_maps(&mut normalized);
+
+    Ok(with_comments(
+        &format,
+        content,
+        &format.serialize(&normalized)?,
+    ))
+}
+
+/// Lays out a serialized config the way laio configs are written and puts back
+/// the comments and blank lines of its original content.
+pub(crate) fn with_comments(format: &Format, content: &str, formatted: &str) -> String {
+    match format {
+        Format::Yaml => Comments::parse(content).apply(&indent_sequences(formatted)),
+        Format::Toml => Comments::parse(content).apply(formatted),
+        Format::Json => format!("{}\n", formatted),
+    }
+}
+
+// Indents block sequences below their key, the way laio configs are written.
+fn indent_sequences(yaml: &str) -> String {
+    // indentation of the items of the sequences the current line is in
+    let mut sequences: Vec<usize> = vec![];
+    let mut previous = "";
+    let mut lines = vec![];
+    for line in yaml.lines() {
+        let indent = indentation(line);
+        let item = line.trim_start().starts_with("- ") || line.trim() == "-";
+        sequences.retain(|&seq| seq < indent || (seq == indent && item));
+        if item
+            && previous.ends_with(':')
+            && key_indentation(previous) == indent
+            && sequences.last() != Some(&indent)
+        {
+            sequences.push(indent);
+        }
+        lines.push(format!("{}{}", "  ".repeat(sequences.len()), line));
+        previous = line;
+    }
+    lines.join("\n")
+}
+
+// Removes the `include` entries of the windows, with their position.
+fn take_includes(session: &mut Mapping) -> Vec<(usize, Value)> {
+    let Some(Value::Sequence(windows)) = session.get_mut("windows") else {
+        return vec![];
+    };
+    let (includes, rest): (Vec<_>, Vec<_>) = take(windows)
+        .into_iter()
+        .enumerate()
+        .partition(|(_, window)| window.get(INCLUDE).is_some());
+    *windows = rest.into_iter().map(|(_, window)| window).collect();
+    includes
+}
+
+fn sort_maps(value: &mut Value) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                if let (Some(key), Value::Mapping(map)) = (key.as_str(), &mut *value) {
+                    if MAPS.contains(&key) {
+                        let mut entries: Vec<_> = take(map).into_iter().collect();
+                        entries.sort_by(|(a, _), (b, _)| a.as_str().cmp(&b.as_str()));
+                        map.extend(entries);
+                        continue;
+                    }
+                }
+                sort_maps(value);
+            }
+        }
+        Value::Sequence(values) => values.iter_mut().for_each(sort_maps),
+        _ => {}
+    }
+}
+
+/// A line of a config with the comments and blank lines around it.
+struct Commented {
+    code: String,
+    indent: usize,
+    leading: Vec<String>,
+    trailing: Option<String>,
+    used: bool,
+}
+
+/// Comments of a config, attached to the line they precede or end.
+struct Comments {
+    header: Option<String>,
+    lines: Vec<Commented>,
+    footer: Vec<String>,
+}
+
+impl Comments {
+    fn parse(content: &str) -> Self {
+        let mut comments = Comments {
+            header: None,
+            lines: vec![],
+            footer: vec![],
+        };
+        let mut pending = vec![];
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed == "---" && comments.lines.is_empty() {
+                comments.header = Some(trimmed.to_string());
+            } else if trimmed.is_empty() || trimmed.starts_with('#') {
+                pending.push(trimmed.to_string());
+            } else {
+                let (code, trailing) = split_comment(line);
+                comments.lines.push(Commented {
+                    code: normalize_line(code),
+                    indent: indentation(line),
+                    leading: take(&mut pending),
+                    trailing: trailing.map(String::from),
+                    used: false,
+                });
+            }
+        }
+        comments.footer = pending;
+        comments
+    }
+
+    /// Puts the comments back into the formatted config, matching lines by content.
+    fn apply(mut self, formatted: &str) -> String {
+        let mut output: Vec<String> = self.header.take().into_iter().collect();
+        let mut cursor = 0;
+        for line in formatted.lines() {
+            let code = normalize_line(line);
+            let indent = indentation(line);
+            let candidates = || (cursor..self.lines.len()).chain(0..cursor);
+            let matches = |idx: &usize| !self.lines[*idx].used && self.lines[*idx].code == code;
+            // lines at the same indentation first, keys like `env` repeat on every level
+            let found = candidates()
+                .filter(matches)
+                .find(|&idx| self.lines[idx].indent == indent)
+                .or_else(|| candidates().find(matches))
+                .or_else(|| self.renamed(cursor, &code, indent));
+            let Some(idx) = found else {
+                output.push(line.to_string());
+                continue;
+            };
+            cursor = idx + 1;
+            let commented = &mut self.lines[idx];
+            commented.used = true;
+            for leading in &commented.leading {
+                match leading.is_empty() {
+                    // no blank lines before the first line or after another one
+                    true if output
+                        .last()
+                        .is_none_or(|line| line.is_empty() || line == "---") => {}
+                    true => output.push(String::new()),
+                    false => output.push(format!("{}{}", " ".repeat(indent), leading)),
+                }
+            }
+            output.push(match &commented.trailing {
+                Some(comment) => format!("{} {}", line, comment),
+                None => line.to_string(),
+            });
+        }
+        while self.footer.last().is_some_and(String::is_empty) {
+            self.footer.pop();
+        }
+        output.extend(self.footer);
+        output.push(String::new());
+        output.join("\n")
+    }
+
+    // The next line if it only differs in its key, like keys renamed by a migration.
+    fn renamed(&self, cursor: usize, code: &str, indent: usize) -> Option<usize> {
+        let next = self.lines.get(cursor).filter(|next| !next.used)?;
+        let (_, value) = code.split_once(':')?;
+        let (_, next_value) = next.code.split_once(':')?;
+        (next.indent == indent && value == next_value).then_some(cursor)
+    }
+}
+
+// Splits a line into its code and trailing comment, `#` only starts a comment
+// outside of quotes and after whitespace.
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+    for (idx, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                previous = c;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') if previous.is_whitespace() => {
+                return (line[..idx].trim_end(), Some(&line[idx..]));
+            }
+            _ => {}
+        }
+        escaped = false;
+        previous = c;
+    }
+    (line, None)
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+// Indentation of the key of a line, which follows the dash of sequence items.
+fn key_indentation(line: &str) -> usize {
+    match line.trim_start().strip_prefix("- ") {
+        Some(key) => line.len() - key.len(),
+        None => indentation(line),
+    }
+}
+
+// Lines are compared without quotes and repeated whitespace, the serializers
+// quote differently than people do.
+fn normalize_line(line: &str) -> String {
+    line.replace(['"', '\''], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,115 @@
+use std::fmt::Display;
+
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{
+        InstanceType, NumberValidation, Schema, SchemaObject, StringValidation, SubschemaValidation,
+    },
+    JsonSchema,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Size of a pane along the split direction, absolute in cells or relative
+/// to the parent, written as `120` or `"30%"`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Size {
+    Cells(usize),
+    Percent(usize),
+}
+
+impl Size {
+    pub(crate) fn resolve(&self, total: usize) -> usize {
+        match self {
+            Size::Cells(cells) => *cells,
+            Size::Percent(percent) => total * percent / 100,
+        }
+    }
+}
+
+impl Display for Size {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Size::Cells(cells) => write!(f, "{}", cells),
+            Size::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl TryFrom<&str> for Size {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let size = match value.trim().strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse()
+                .ok()
+                .filter(|percent| (1..=100).contains(percent))
+                .map(Size::Percent),
+            None => value.trim().parse().ok().map(Size::Cells),
+        };
+        size.filter(|size| *size != Size::Cells(0)).ok_or(format!(
+            "invalid size '{}', expected cells (e.g. 120) or a percentage between 1% and 100%",
+            value
+        ))
+    }
+}
+
+impl Serialize for Size {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Size::Cells(cells) => serializer.serialize_u64(*cells as u64),
+            Size::Percent(_) => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Size {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Cells(usize),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Cells(cells) => Size::try_from(cells.to_string().as_str()),
+            Raw::Text(text) => Size::try_from(text.as_str()),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+impl JsonSchema for Size {
+    fn schema_name() -> String {
+        "Size".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        let cells = SchemaObject {
+            instance_type: Some(InstanceType::Integer.into()),
+            number: Some(Box::new(NumberValidation {
+                minimum: Some(1.0),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let percent = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some("^([1-9][0-9]?|100)%$".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![cells.into(), percent.into()]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
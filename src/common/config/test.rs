@@ -0,0 +1,856 @@
+use std::{
+    collections::HashMap,
+    env::set_var,
+    fs::read_to_string,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::common::path::to_absolute_path;
+use miette::{Diagnostic, Result};
+use serde_json::Value;
+
+use super::{
+    format::{config_file, with_extension, Format},
+    layout::resolve_sizes,
+    lint::{Rule, Severity},
+    normalize::normalize,
+    version::{migrate, VERSION},
+    wait::OnTimeout,
+    Command, Condition, FlexDirection, Layout, Pane, Session, Size, WaitFor,
+};
+
+const CONFIG_DIR: &str = "src/common/config/test";
+
+#[test]
+fn session_interpolate_vars() -> Result<()> {
+    set_var("LAIO_TEST_SUBDIR", "src");
+    let path = PathBuf::from_str("src/common/config/test/vars.yaml").unwrap();
+
+    let session = Session::from_config(
+        &path,
+        Path::new(CONFIG_DIR),
+        &HashMap::from([("branch".to_string(), "feature".to_string())]),
+    )?;
+
+    assert_eq!(session.name, "vars");
+    assert_eq!(session.path, "/tmp/vars");
+    assert_eq!(session.env.get("BRANCH"), Some(&"feature".to_string()));
+    assert_eq!(session.startup[0].args, vec!["checkout", "feature"]);
+    assert_eq!(session.windows[0].panes[0].path, "src");
+    assert_eq!(
+        session.windows[0].panes[0].commands[0].args,
+        vec!["${branch}"]
+    );
+    Ok(())
+}
+
+#[test]
+fn session_interpolate_unresolved() {
+    let path = PathBuf::from_str("src/common/config/test/unresolved.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected unresolved variables");
+    let problems: Vec<String> = err
+        .related()
+        .expect("Expected problems")
+        .map(|problem| problem.to_string())
+        .collect();
+
+    assert_eq!(
+        problems,
+        vec![
+            "windows[0].panes[0].commands[0].args[0]: Variable 'also_missing' is not set",
+            "path: Variable 'missing' is not set",
+        ]
+    );
+    assert!(err.related().unwrap().all(|problem| problem
+        .labels()
+        .is_some_and(|mut labels| labels.next().is_some())));
+}
+
+#[test]
+fn session_interpolate_validated() {
+    let path = PathBuf::from_str("src/common/config/test/vars_invalid.yaml").unwrap();
+
+    let err = Session::from_config(
+        &path,
+        Path::new(CONFIG_DIR),
+        &HashMap::from([("window".to_string(), "x".to_string())]),
+    )
+    .expect_err("Expected the resolved values to fail validation");
+    let problems: Vec<String> = err
+        .related()
+        .expect("Expected problems")
+        .map(|problem| problem.to_string())
+        .collect();
+
+    assert_eq!(problems.len(), 2, "{:#?}", problems);
+    assert!(problems[0].starts_with("name: "), "{:#?}", problems);
+    assert!(
+        problems[1].starts_with("windows[0].name: "),
+        "{:#?}",
+        problems
+    );
+}
+
+#[test]
+fn session_extends() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/extends/project.yaml").unwrap();
+
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    assert_eq!(session.name, "project");
+    assert_eq!(session.path, "/tmp/project");
+    assert_eq!(session.extends, Some("base".to_string()));
+    assert_eq!(session.env.get("FOO"), Some(&"project".to_string()));
+    assert_eq!(session.env.get("BAR"), Some(&"base".to_string()));
+    assert_eq!(session.startup[0].args, vec!["base"]);
+    assert_eq!(
+        session
+            .windows
+            .iter()
+            .map(|w| w.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["code", "shell", "logs", "k8s"]
+    );
+    assert_eq!(session.windows[1].flex_direction, FlexDirection::Row);
+    assert_eq!(session.windows[1].panes.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn session_extends_config_dir() -> Result<()> {
+    // no base next to the local config, it is looked up in the config dir
+    let config_dir = to_absolute_path("src/common/config/test/extends")?;
+    let path = PathBuf::from_str("src/common/config/test/extends/local/.laio.yaml").unwrap();
+
+    let session = Session::from_config(&path, &config_dir, &HashMap::new())?;
+
+    assert_eq!(session.name, "local");
+    assert_eq!(session.extends, Some("base".to_string()));
+    assert_eq!(session.env.get("BAR"), Some(&"base".to_string()));
+    Ok(())
+}
+
+#[test]
+fn session_extends_cycle() {
+    let path = PathBuf::from_str("src/common/config/test/extends/cycle_a.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected cyclic extends");
+
+    assert!(format!("{:?}", err).contains("Cyclic extends detected"));
+}
+
+#[test]
+fn session_include() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/include/session.yaml").unwrap();
+
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    assert_eq!(
+        session
+            .windows
+            .iter()
+            .map(|w| w.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["code", "k8s", "htop", "logs"]
+    );
+    assert_eq!(session.windows[1].flex_direction, FlexDirection::Column);
+    assert_eq!(session.windows[1].panes[0].flex, 3);
+    Ok(())
+}
+
+#[test]
+fn session_include_invalid_fragment() {
+    let path = PathBuf::from_str("src/common/config/test/include/invalid.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected invalid fragment");
+    let err = format!("{:?}", err);
+
+    assert!(err.contains("fragments/invalid.yaml"));
+    assert!(err.contains("name: Window names should have at least 3 characters."));
+}
+
+#[test]
+fn session_when() -> Result<()> {
+    set_var("LAIO_TEST_WHEN", "on");
+    let path = PathBuf::from_str("src/common/config/test/when.yaml").unwrap();
+
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    assert_eq!(
+        session
+            .active_windows()
+            .iter()
+            .map(|w| w.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["code", "fixtures"]
+    );
+    assert_eq!(
+        session.windows[0]
+            .active_panes()
+            .iter()
+            .map(|p| p.name.as_deref().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["editor", "shell"]
+    );
+    assert_eq!(
+        session.windows[2]
+            .active_panes()
+            .iter()
+            .map(|p| p.name.as_deref().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["other"]
+    );
+    Ok(())
+}
+
+#[test]
+fn condition_env() {
+    set_var("LAIO_TEST_CONDITION", "yes");
+    let condition = |env: &str| Condition {
+        env: Some(env.to_string()),
+        ..Default::default()
+    };
+
+    assert!(condition("LAIO_TEST_CONDITION").matches());
+    assert!(condition("LAIO_TEST_CONDITION=yes").matches());
+    assert!(!condition("LAIO_TEST_CONDITION=no").matches());
+    assert!(!condition("LAIO_TEST_CONDITION_UNSET").matches());
+}
+
+#[test]
+fn session_json_schema() -> Result<()> {
+    let schema: Value = serde_json::from_str(&Session::json_schema()?).unwrap();
+
+    assert_eq!(schema["properties"]["name"]["minLength"], 3);
+    assert_eq!(schema["properties"]["windows"]["minItems"], 1);
+    assert_eq!(
+        schema["definitions"]["Window"]["properties"]["name"]["minLength"],
+        3
+    );
+    assert_eq!(
+        schema["definitions"]["Pane"]["properties"]["flex"]["minimum"],
+        1.0
+    );
+    assert_eq!(
+        schema["definitions"]["Pane"]["properties"]["zoom"]["description"],
+        "Zoom the pane once the session is started, at most one per window."
+    );
+
+    // the bundled schema.json is generated by `laio config schema`
+    let bundled: Value =
+        serde_json::from_str(include_str!("schema.json")).expect("schema.json is valid json");
+    assert_eq!(schema, bundled);
+    Ok(())
+}
+
+#[test]
+fn session_formats() -> Result<()> {
+    let load = |file: &str| -> Result<String> {
+        let path = PathBuf::from(format!("src/common/config/test/formats/{}", file));
+        Ok(serde_yaml::to_string(&Session::from_config(
+            &path,
+            Path::new(CONFIG_DIR),
+            &HashMap::new(),
+        )?)
+        .unwrap())
+    };
+
+    let yaml = load("session.yaml")?;
+    assert_eq!(load("session.toml")?, yaml);
+    assert_eq!(load("session.json")?, yaml);
+    assert_eq!(
+        load("child.yml")?,
+        yaml.replace("name: formats\n", "name: child\nextends: session.toml\n")
+    );
+    Ok(())
+}
+
+#[test]
+fn config_file_extensions() {
+    let dir = Path::new("src/common/config/test/formats");
+
+    assert_eq!(config_file(dir, "session"), dir.join("session.yaml"));
+    assert_eq!(config_file(dir, "child"), dir.join("child.yml"));
+    assert_eq!(config_file(dir, "missing"), dir.join("missing.yaml"));
+    assert_eq!(
+        with_extension(&dir.join("child.yaml")),
+        dir.join("child.yml")
+    );
+    assert_eq!(Format::from_path(Path::new("a.toml")), Format::Toml);
+    assert_eq!(Format::from_path(Path::new(".laio.json")), Format::Json);
+    assert_eq!(Format::from_path(Path::new("a.yml")), Format::Yaml);
+}
+
+#[test]
+fn session_diagnostics() {
+    let path = PathBuf::from_str("src/common/config/test/diagnostics.yaml").unwrap();
+    let source = read_to_string(&path).unwrap();
+    let offset = |line: usize, column: usize| {
+        source
+            .lines()
+            .take(line - 1)
+            .map(|l| l.len() + 1)
+            .sum::<usize>()
+            + column
+            - 1
+    };
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected diagnostics");
+    let problems = err
+        .related()
+        .expect("Expected related problems")
+        .map(|problem| {
+            (
+                problem.to_string(),
+                problem
+                    .labels()
+                    .into_iter()
+                    .flatten()
+                    .map(|label| label.offset())
+                    .collect::<Vec<_>>(),
+                problem.help().is_some(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        problems,
+        vec![
+            (
+                "name: The session name should have at least 3 characters.".to_string(),
+                vec![offset(2, 7)],
+                true
+            ),
+            (
+                "windows[0].panes[0].flex: Flex has to be >= 1".to_string(),
+                vec![offset(7, 15)],
+                true
+            ),
+            (
+                "windows[1].name: Window names should have at least 3 characters.".to_string(),
+                vec![offset(10, 11)],
+                true
+            ),
+            (
+                "windows[0]: Window 'code' has more than one pane with zoom enabled".to_string(),
+                vec![offset(8, 15), offset(9, 15)],
+                true
+            ),
+        ]
+    );
+}
+
+#[test]
+fn format_syntax_error() {
+    let err = Format::Yaml
+        .parse("name: abc\nwindows:\n  - name: [code\n")
+        .expect_err("Expected syntax error");
+
+    assert!(err.to_string().contains("did not find expected ',' or ']'"));
+    assert_eq!(
+        err.labels()
+            .into_iter()
+            .flatten()
+            .next()
+            .map(|l| l.offset()),
+        Some(34)
+    );
+}
+
+#[test]
+fn pane_sizes() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/sizes.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let panes = &session.windows[0].panes;
+    assert_eq!(panes[0].size, Some(Size::Cells(30)));
+    assert_eq!(panes[1].min_size, Some(Size::Percent(40)));
+    assert_eq!(panes[2].max_size, Some(Size::Cells(20)));
+    assert_eq!(session.windows[1].panes[0].size, Some(Size::Percent(25)));
+
+    assert_eq!(Size::try_from("100%"), Ok(Size::Percent(100)));
+    assert!(Size::try_from("0").is_err());
+    assert!(Size::try_from("120%").is_err());
+    assert!(Size::try_from("half").is_err());
+    Ok(())
+}
+
+#[test]
+fn session_multiple_focus() {
+    let path = PathBuf::from_str("src/common/config/test/multi_focus.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected multiple focus");
+    let problems = err
+        .related()
+        .expect("Expected related problems")
+        .map(|problem| {
+            (
+                problem.to_string(),
+                problem.labels().into_iter().flatten().count(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        problems,
+        vec![
+            (
+                "windows: Session 'multi_focus' has more than one window with focus enabled"
+                    .to_string(),
+                2
+            ),
+            (
+                "windows[0]: Window 'code' has more than one pane with focus enabled".to_string(),
+                2
+            ),
+        ]
+    );
+}
+
+#[test]
+fn session_wait_for() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/wait_for/valid.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let wait_for = session.startup[1].wait_for.as_ref().unwrap();
+    assert_eq!(wait_for.port, Some(5432));
+    assert_eq!(wait_for.timeout, 60);
+    let wait_for = session.windows[0].panes[0].wait_for.as_ref().unwrap();
+    assert_eq!(wait_for.pane.as_deref(), Some("api"));
+    assert_eq!(wait_for.on_timeout, OnTimeout::Continue);
+    assert_eq!(wait_for.timeout, 30);
+    Ok(())
+}
+
+#[test]
+fn session_wait_for_invalid() {
+    let path = PathBuf::from_str("src/common/config/test/wait_for/invalid.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected invalid gates");
+    let problems = err
+        .related()
+        .expect("Expected related problems")
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        problems,
+        vec![
+            "startup[0].wait_for: Unable to wait for the output of pane 'api' here",
+            "windows[0].panes[0].wait_for: wait_for references unknown pane 'db'",
+            "windows[0].panes[1].wait_for: wait_for needs both pane and pattern to match pane output",
+            "windows[0].panes[1].commands[0].wait_for: Invalid wait_for pattern: regex parse error:\n    [ready\n    ^\nerror: unclosed character class",
+        ]
+    );
+}
+
+#[test]
+fn wait_for_gates() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let no_output = |_: &str| -> Result<String> { Ok(String::new()) };
+    let path = "src/common/config/test".to_string();
+
+    let gate = |wait_for: WaitFor| WaitFor {
+        timeout: 0,
+        ..wait_for
+    };
+
+    gate(WaitFor {
+        port: Some(port),
+        file: Some("wait_for/valid.yaml".to_string()),
+        ..Default::default()
+    })
+    .wait(&path, &no_output)?;
+    gate(WaitFor {
+        pane: Some("api".to_string()),
+        pattern: Some(r"listening on \d+".to_string()),
+        ..Default::default()
+    })
+    .wait(&path, &|pane| Ok(format!("{} listening on 8080", pane)))?;
+
+    let missing = WaitFor {
+        file: Some("wait_for/missing.yaml".to_string()),
+        ..Default::default()
+    };
+    let err = gate(missing.clone())
+        .wait(&path, &no_output)
+        .expect_err("Expected timeout");
+    assert_eq!(
+        err.to_string(),
+        "Timed out after 0s waiting for file wait_for/missing.yaml"
+    );
+    gate(WaitFor {
+        on_timeout: OnTimeout::Continue,
+        ..missing
+    })
+    .wait(&path, &no_output)?;
+    Ok(())
+}
+
+#[test]
+fn command_options() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/commands.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let panes = &session.windows[0].panes;
+    assert!(!panes[0].commands[0].enter);
+    assert!(!panes[0].commands[0].literal);
+    assert!(panes[1].commands[0].enter);
+    assert!(panes[1].commands[0].literal);
+    assert_eq!(panes[1].commands[0].delay_ms, 1500);
+    assert_eq!(panes[1].commands[1], Command::from_string("ls"));
+    Ok(())
+}
+
+#[test]
+fn session_parallel_invalid() {
+    let path = PathBuf::from_str("src/common/config/test/parallel/invalid.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected invalid groups");
+    let problems = err
+        .related()
+        .expect("Expected related problems")
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        problems,
+        vec![
+            "startup[0]: A command either runs a `command` or a `parallel` group",
+            "startup[0].parallel[1].parallel: parallel groups can not be nested",
+            "windows[0].panes[0].commands[0].parallel: parallel groups are only supported in startup, shutdown and hooks",
+        ]
+    );
+}
+
+#[test]
+fn session_exec_invalid() {
+    let path = PathBuf::from_str("src/common/config/test/exec_invalid.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected invalid exec");
+    let problems = err
+        .related()
+        .expect("Expected related problems")
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        problems,
+        vec![
+            "windows[0]: Window 'code' has panes with nested panes that exec their commands",
+            "windows[0]: Window 'code' has panes with no commands to exec",
+        ]
+    );
+}
+
+#[test]
+fn session_layouts() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/layouts.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    fn names(panes: &[Pane]) -> Vec<String> {
+        panes
+            .iter()
+            .map(|pane| match &pane.name {
+                Some(name) => name.clone(),
+                None => format!("[{}]", names(&pane.panes).join(" ")),
+            })
+            .collect()
+    }
+
+    let code = &session.windows[0];
+    assert_eq!(code.layout, Some(Layout::MainVertical));
+    let (direction, panes) = Layout::MainVertical.arrange(&code.panes);
+    assert_eq!(direction, FlexDirection::Row);
+    assert_eq!(names(&panes), vec!["editor", "[tests shell]"]);
+    assert_eq!(panes[1].flex_direction, FlexDirection::Column);
+
+    let (direction, panes) = Layout::Tiled.arrange(&session.windows[1].panes);
+    assert_eq!(direction, FlexDirection::Column);
+    assert_eq!(names(&panes), vec!["[one two]", "[three four]", "five"]);
+    Ok(())
+}
+
+#[test]
+fn session_layouts_nested() {
+    let path = PathBuf::from_str("src/common/config/test/layouts_invalid.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected nested panes");
+    let problems = err
+        .related()
+        .expect("Expected related problems")
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        problems,
+        vec![
+            "windows[0].layout: Window 'code' uses the even-horizontal layout but has nested panes"
+        ]
+    );
+}
+
+#[test]
+fn session_grid() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/grid.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    fn names(panes: &[Pane]) -> Vec<String> {
+        panes
+            .iter()
+            .map(|pane| match &pane.name {
+                Some(name) => format!("{}:{}", name, pane.flex),
+                None => format!("[{}]:{}", names(&pane.panes).join(" "), pane.flex),
+            })
+            .collect()
+    }
+
+    let (direction, panes) = session.windows[0].flex_panes();
+    assert_eq!(direction, FlexDirection::Column);
+    assert_eq!(
+        names(&panes),
+        vec!["[cpu:1 mem:1 disk:1]:1", "[net:1 logs:1]:1"]
+    );
+    assert_eq!(panes[0].flex_direction, FlexDirection::Row);
+
+    let (direction, panes) = session.windows[1].flex_panes();
+    assert_eq!(direction, FlexDirection::Row);
+    assert_eq!(names(&panes), vec!["main:1", "[[a:1 b:1]:1 c:1]:2"]);
+    assert_eq!(panes[1].flex_direction, FlexDirection::Column);
+    Ok(())
+}
+
+#[test]
+fn session_grid_invalid() {
+    let path = PathBuf::from_str("src/common/config/test/grid_invalid.yaml").unwrap();
+
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected invalid grids");
+    let problems = err
+        .related()
+        .expect("Expected related problems")
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        problems,
+        vec![
+            "windows[0].grid: Panes 1, 2, 3, 4, 5 can not be split into rows and columns",
+            "windows[1].grid: Pane 3 does not fit into the 1x2 grid",
+            "windows[2].grid: Window 'nested' uses a 1x2 grid but has nested panes",
+        ]
+    );
+}
+
+#[test]
+fn session_version() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/version/legacy.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    assert_eq!(session.version, VERSION);
+    assert_eq!(session.startup, vec![Command::from_string("echo hello")]);
+
+    let path = PathBuf::from_str("src/common/config/test/version/newer.yaml").unwrap();
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected newer version");
+    assert!(format!("{:?}", err).contains("Config version 99 is newer than version"));
+
+    let path = PathBuf::from_str("src/common/config/test/version/current.yaml").unwrap();
+    let err = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())
+        .expect_err("Expected unknown field");
+    assert!(format!("{:?}", err).contains("unknown field `commands`"));
+    Ok(())
+}
+
+#[test]
+fn migrate_legacy() -> Result<()> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(
+        r#"
+name: legacy
+commands:
+  - command: echo
+windows: []
+"#,
+    )
+    .unwrap();
+
+    let deprecations = migrate(&mut value)?;
+    assert_eq!(
+        deprecations,
+        vec!["'commands' is deprecated, use 'startup' instead"]
+    );
+    let keys: Vec<&str> = value
+        .as_mapping()
+        .unwrap()
+        .keys()
+        .filter_map(|key| key.as_str())
+        .collect();
+    assert_eq!(keys, vec!["version", "name", "startup", "windows"]);
+
+    let migrated = value.clone();
+    assert!(migrate(&mut value)?.is_empty());
+    assert_eq!(value, migrated);
+
+    let mut value: serde_yaml::Value = serde_yaml::from_str("commands: []\nstartup: []").unwrap();
+    migrate(&mut value).expect_err("Expected both commands and startup to fail");
+    Ok(())
+}
+
+#[test]
+fn normalize_config() -> Result<()> {
+    let messy = Path::new("src/common/config/test/fmt/messy.yaml");
+    let formatted = Path::new("src/common/config/test/fmt/formatted.yaml");
+    let expected = read_to_string(formatted).unwrap();
+
+    assert_eq!(normalize(messy, &read_to_string(messy).unwrap())?, expected);
+    assert_eq!(normalize(formatted, &expected)?, expected);
+
+    let legacy = Path::new("src/common/config/test/version/legacy.yaml");
+    let err = normalize(legacy, &read_to_string(legacy).unwrap())
+        .expect_err("Expected deprecated fields to fail");
+    assert!(err.to_string().contains("run `laio config migrate` first"));
+    Ok(())
+}
+
+#[test]
+fn normalize_config_lint() -> Result<()> {
+    let path = Path::new("src/common/config/test/lint/configured.yaml");
+    let content = read_to_string(path).unwrap();
+    let formatted = normalize(path, &content)?;
+
+    // the lint map is sorted like env and vars, not left in hash order
+    assert!(formatted.contains(
+        "lint:\n  command-not-found: off\n  duplicate-name: error\n  session-name: warning\n"
+    ));
+    assert_eq!(normalize(path, &content)?, formatted);
+    assert_eq!(normalize(path, &formatted)?, formatted);
+    Ok(())
+}
+
+#[test]
+fn normalize_config_formats() -> Result<()> {
+    for name in ["session.toml", "session.json", "child.yml"] {
+        let path = PathBuf::from("src/common/config/test/formats").join(name);
+        let formatted = normalize(&path, &read_to_string(&path).unwrap())?;
+        assert_eq!(normalize(&path, &formatted)?, formatted, "{}", name);
+        assert!(!formatted.contains("flex = 1"), "{}", name);
+    }
+    Ok(())
+}
+
+#[test]
+fn session_lint() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/lint/lint.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let findings: Vec<(String, Severity, String)> = session
+        .lint()
+        .iter()
+        .map(|finding| {
+            (
+                finding.rule.code().to_string(),
+                finding.severity,
+                finding.to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        findings,
+        vec![
+            (
+                "L006".to_string(),
+                Severity::Error,
+                "name: tmux does not allow '.' or ':' in session name 'my.project'".to_string()
+            ),
+            (
+                "L003".to_string(),
+                Severity::Warning,
+                "windows[1].name: The window name 'code' is used 2 times".to_string()
+            ),
+            (
+                "L003".to_string(),
+                Severity::Warning,
+                "windows[0].panes[0].panes[1].name: The pane name 'shell' is used 2 times"
+                    .to_string()
+            ),
+            (
+                "L001".to_string(),
+                Severity::Warning,
+                "windows[0].panes[0].path: Path '/laio/missing/path' does not exist".to_string()
+            ),
+            (
+                "L002".to_string(),
+                Severity::Warning,
+                "startup[0].command: Command 'laio-missing-command' was not found on PATH"
+                    .to_string()
+            ),
+            (
+                "L005".to_string(),
+                Severity::Warning,
+                "windows[0].panes[0].zoom: Zoom on a pane with nested panes".to_string()
+            ),
+            (
+                "L004".to_string(),
+                Severity::Warning,
+                "windows[1]: Window 'code' has 21 panes smaller than 10x3 in a 200x50 terminal, the smallest is 8x47".to_string()
+            ),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn session_lint_severity() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/lint/configured.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let findings: Vec<(Rule, Severity)> = session
+        .lint()
+        .iter()
+        .map(|finding| (finding.rule, finding.severity))
+        .collect();
+    assert_eq!(
+        findings,
+        vec![
+            (Rule::SessionName, Severity::Warning),
+            (Rule::DuplicateName, Severity::Error),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn layout_resolve_sizes() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/sizes.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    assert_eq!(
+        resolve_sizes(&session.windows[0].panes, 160, &[1; 3]),
+        vec![30, 108, 20]
+    );
+    assert_eq!(
+        resolve_sizes(&session.windows[1].panes, 90, &[1; 2]),
+        vec![22, 67]
+    );
+    // sizes that do not fit are shrunk from the end
+    assert_eq!(
+        resolve_sizes(&session.windows[0].panes, 40, &[1; 3]),
+        vec![30, 7, 1]
+    );
+    // but not below what nested panes require
+    assert_eq!(
+        resolve_sizes(&session.windows[0].panes, 40, &[1, 1, 5]),
+        vec![30, 3, 5]
+    );
+    Ok(())
+}
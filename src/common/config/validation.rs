@@ -1,52 +1,335 @@
-use miette::Report;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceSpan};
 use serde_valid::validation::{Error, Errors};
+use serde_yaml::Value;
+use thiserror::Error;
+use yaml_rust2::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, TScalarStyle},
+};
+
+use super::format::Format;
+
+/// A config that failed to load, with every problem found labeled in its source.
+#[derive(Debug, Error, Diagnostic)]
+#[error("Failed to parse {kind}: {path:?}")]
+pub(crate) struct ConfigError {
+    kind: &'static str,
+    path: PathBuf,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[related]
+    problems: Vec<Problem>,
+}
+
+impl ConfigError {
+    /// `resolved` is the value the problems were found in. Spans are only attached
+    /// where it matches the source, e.g. not for windows merged in via extends.
+    pub(crate) fn new(
+        kind: &'static str,
+        path: &Path,
+        source: String,
+        mut problems: Vec<Problem>,
+        resolved: Option<&Value>,
+    ) -> Self {
+        Self {
+            kind,
+            path: path.to_path_buf(),
+            source_code: locate(path, source, problems.iter_mut(), resolved),
+            problems,
+        }
+    }
+}
+
+/// Labels the targets of the problems in the source of the config at `path`,
+/// see [`ConfigError::new`] for `resolved`.
+pub(crate) fn locate<'a>(
+    path: &Path,
+    source: String,
+    problems: impl Iterator<Item = &'a mut Problem>,
+    resolved: Option<&Value>,
+) -> NamedSource<String> {
+    let format = Format::from_path(path);
+    let spans = match format {
+        Format::Toml => Spans::default(),
+        _ => Spans::parse(&source),
+    };
+    let original = format.parse(&source).ok();
+
+    for problem in problems {
+        let labels: Vec<LabeledSpan> = problem
+            .targets
+            .iter()
+            .filter(|target| match (resolved, &original) {
+                (Some(resolved), Some(original)) => {
+                    value_at(resolved, target) == value_at(original, target)
+                }
+                _ => true,
+            })
+            .filter_map(|target| spans.get(target))
+            .map(|span| LabeledSpan::new_with_span(Some(problem.label.clone()), span))
+            .collect();
+        problem.labels.extend(labels);
+    }
+
+    NamedSource::new(path.to_string_lossy(), source).with_language("yaml")
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("{}", describe(path, message))]
+pub(crate) struct Problem {
+    path: String,
+    message: String,
+    label: String,
+    targets: Vec<String>,
+    #[help]
+    help: Option<String>,
+    #[label(collection)]
+    labels: Vec<LabeledSpan>,
+}
+
+fn describe(path: &str, message: &str) -> String {
+    match path {
+        "" => message.to_string(),
+        path => format!("{}: {}", path, message),
+    }
+}
+
+impl Problem {
+    pub(crate) fn new(path: &str, message: impl Display, label: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            message: message.to_string(),
+            label: label.to_string(),
+            targets: vec![path.to_string()],
+            help: None,
+            labels: vec![],
+        }
+    }
+
+    /// Labels `targets` in the source instead of the problem path itself.
+    pub(crate) fn at(mut self, targets: Vec<String>) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// Moves the problem below `prefix`, e.g. a window into the windows of a config.
+    pub(crate) fn prefixed(mut self, prefix: &str) -> Self {
+        self.path = join(prefix, &self.path);
+        self.targets = self
+            .targets
+            .iter()
+            .map(|target| join(prefix, target))
+            .collect();
+        self
+    }
+
+    pub(crate) fn with_help(mut self, help: impl Display) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    /// Points the problem at a byte offset of the source, e.g. for syntax errors.
+    pub(crate) fn at_offset(mut self, offset: usize) -> Self {
+        self.targets = vec![];
+        self.labels = vec![LabeledSpan::new_with_span(
+            Some(self.label.clone()),
+            SourceSpan::from((offset, 1)),
+        )];
+        self
+    }
+}
 
-pub(crate) fn generate_report(errors: Option<&Errors>) -> Report {
-    build_error_tree(errors, "session")
+pub(crate) fn deserialize_problem(
+    error: &serde_path_to_error::Error<serde_yaml::Error>,
+) -> Problem {
+    let path = match error.path().to_string().as_str() {
+        "." => String::new(),
+        path => path.to_string(),
+    };
+    Problem::new(&path, error.inner(), "invalid")
+        .with_help("Run `laio config schema` to see the supported fields and their types.")
 }
 
-fn build_error_tree(errors: Option<&Errors>, prefix: &str) -> Report {
+/// Flattens validation errors into one problem per violated rule.
+pub(crate) fn validation_problems(errors: &Errors, path: &str) -> Vec<Problem> {
     match errors {
-        Some(Errors::Array(array_errors)) => {
-            let mut messages = vec![];
+        Errors::Array(array_errors) => array_errors
+            .errors
+            .iter()
+            .map(|err| rule_problem(err, path))
+            .chain(
+                array_errors
+                    .items
+                    .iter()
+                    .flat_map(|(idx, err)| validation_problems(err, &format!("{}[{}]", path, idx))),
+            )
+            .collect(),
+        Errors::Object(object_errors) => object_errors
+            .errors
+            .iter()
+            .map(|err| rule_problem(err, path))
+            .chain(
+                object_errors
+                    .properties
+                    .iter()
+                    .flat_map(|(field, err)| validation_problems(err, &join(path, field))),
+            )
+            .collect(),
+        Errors::NewType(errors) => errors.iter().map(|err| rule_problem(err, path)).collect(),
+    }
+}
 
-            for (field, (_, err)) in array_errors.items.iter().enumerate() {
-                let nested_report = build_error_tree(Some(err), &format!("{}[{}]", prefix, field));
-                messages.push(format!("{}", nested_report));
-            }
+fn rule_problem(error: &Error, path: &str) -> Problem {
+    let field = path.rsplit(['.', '[']).next().unwrap_or_default();
+    let (label, help) = match (error, field) {
+        (Error::MinLength(_), "name") => (
+            "too short",
+            Some("Names need at least 3 characters, e.g. `name: code`."),
+        ),
+        (Error::MinLength(_), _) => ("too short", None),
+        (Error::Minimum(_), "flex") => (
+            "too small",
+            Some("Flex is the share of space relative to the sibling panes, use 1 or more."),
+        ),
+        (Error::Minimum(_), _) => ("too small", None),
+        (Error::MinItems(_), "windows") => (
+            "no windows",
+            Some("Add at least one window, e.g.\n\nwindows:\n  - name: code"),
+        ),
+        (Error::MinItems(_), _) => ("too few items", None),
+        _ => ("invalid", None),
+    };
+    let problem = Problem::new(path, error, label);
+    match help {
+        Some(help) => problem.with_help(help),
+        None => problem,
+    }
+}
 
-            for (field, err) in array_errors.errors.iter().enumerate() {
-                let single_error_report =
-                    build_single_error_report(&[err.clone()], &format!("{}[{}]", prefix, field));
-                messages.push(format!("{}", single_error_report));
-            }
+fn join(path: &str, field: &str) -> String {
+    match (path, field) {
+        ("", field) => field.to_string(),
+        (path, "") => path.to_string(),
+        (path, field) if field.starts_with('[') => format!("{}{}", path, field),
+        (path, field) => format!("{}.{}", path, field),
+    }
+}
 
-            Report::msg(messages.join("\n"))
+fn value_at<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut value = value;
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let mut parts = segment.split('[');
+        if let Some(key) = parts.next().filter(|key| !key.is_empty()) {
+            value = value.get(key)?;
+        }
+        for idx in parts {
+            value = value.get(idx.trim_end_matches(']').parse::<usize>().ok()?)?;
         }
+    }
+    Some(value)
+}
 
-        Some(Errors::Object(object_errors)) => {
-            let messages = object_errors
-                .properties
-                .iter()
-                .map(|(field, err)| build_error_tree(Some(err), &format!("{}.{}", prefix, field)))
-                .map(|r| format!("{}", r))
-                .collect::<Vec<_>>();
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+// Source spans of the nodes in a yaml (or json) document, keyed by their path,
+// e.g. `windows[1].name`. Collections are located by their key.
+#[derive(Default)]
+struct Spans {
+    chars: Vec<usize>,
+    spans: HashMap<String, SourceSpan>,
+    stack: Vec<Frame>,
+}
 
-            Report::msg(messages.join("\n"))
+impl Spans {
+    fn parse(source: &str) -> Self {
+        let mut spans = Spans {
+            chars: source.char_indices().map(|(offset, _)| offset).collect(),
+            ..Default::default()
+        };
+        if let Err(e) = Parser::new_from_str(source).load(&mut spans, false) {
+            log::debug!("Unable to locate config source spans: {}", e);
         }
+        spans
+    }
 
-        Some(Errors::NewType(new_type_error)) => build_single_error_report(new_type_error, prefix),
+    fn get(&self, path: &str) -> Option<SourceSpan> {
+        self.spans.get(path).copied()
+    }
 
-        None => Report::msg("No errors found."),
+    fn offset(&self, mark: &Marker) -> usize {
+        self.chars
+            .get(mark.index())
+            .copied()
+            .unwrap_or(self.chars.last().map_or(0, |last| last + 1))
     }
-}
 
-fn build_single_error_report(errors: &[Error], prefix: &str) -> Report {
-    let messages = errors
-        .iter()
-        .map(|err| format!("{}: {}", prefix, err))
-        .collect::<Vec<_>>()
-        .join("\n");
+    fn next_path(&self) -> String {
+        match self.stack.last() {
+            Some(Frame::Mapping {
+                path,
+                key: Some(key),
+            }) => join(path, key),
+            Some(Frame::Sequence { path, index }) => format!("{}[{}]", path, index),
+            _ => String::new(),
+        }
+    }
+
+    fn complete(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping { key, .. }) => *key = None,
+            Some(Frame::Sequence { index, .. }) => *index += 1,
+            None => {}
+        }
+    }
+}
 
-    Report::msg(messages)
+impl MarkedEventReceiver for Spans {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let offset = self.offset(&mark);
+        match ev {
+            Event::Scalar(value, style, ..) => {
+                let len = match style {
+                    TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted => value.len() + 2,
+                    TScalarStyle::Plain => value.len(),
+                    _ => 1,
+                };
+                let span = SourceSpan::from((offset, len.max(1)));
+                match self.stack.last_mut() {
+                    Some(Frame::Mapping { path, key }) if key.is_none() => {
+                        self.spans.insert(join(path, &value), span);
+                        *key = Some(value);
+                    }
+                    _ => {
+                        self.spans.insert(self.next_path(), span);
+                        self.complete();
+                    }
+                }
+            }
+            Event::MappingStart(..) | Event::SequenceStart(..) => {
+                let path = self.next_path();
+                self.spans
+                    .entry(path.clone())
+                    .or_insert(SourceSpan::from((offset, 1)));
+                self.stack.push(match ev {
+                    Event::MappingStart(..) => Frame::Mapping { path, key: None },
+                    _ => Frame::Sequence { path, index: 0 },
+                });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                self.complete();
+            }
+            Event::Alias(_) => self.complete(),
+            _ => {}
+        }
+    }
 }
@@ -0,0 +1,191 @@
+use std::{collections::HashMap, env};
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde_yaml::Value;
+
+use super::{validation::Problem, Command, Pane, Session, Window};
+
+lazy_static! {
+    static ref VARIABLE: Regex = Regex::new(r"\$(\$)?\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+}
+
+pub(crate) struct Variables<'a> {
+    values: &'a HashMap<String, String>,
+    unresolved: Vec<String>,
+}
+
+impl<'a> Variables<'a> {
+    pub(crate) fn new(values: &'a HashMap<String, String>) -> Self {
+        Self {
+            values,
+            unresolved: vec![],
+        }
+    }
+
+    /// Replaces `${name}` with the value of `name`, falling back to the environment.
+    /// `$${name}` escapes the expression and yields a literal `${name}`.
+    pub(crate) fn interpolate(&mut self, input: &str) -> String {
+        VARIABLE
+            .replace_all(input, |caps: &Captures| {
+                let name = &caps[2];
+                if caps.get(1).is_some() {
+                    return format!("${{{}}}", name);
+                }
+                match self
+                    .values
+                    .get(name)
+                    .cloned()
+                    .or_else(|| env::var(name).ok())
+                {
+                    Some(value) => value,
+                    None => {
+                        self.unresolved.push(name.to_string());
+                        caps[0].to_string()
+                    }
+                }
+            })
+            .into_owned()
+    }
+
+    pub(crate) fn unresolved(&self) -> Vec<String> {
+        let mut unresolved = self.unresolved.clone();
+        unresolved.sort();
+        unresolved.dedup();
+        unresolved
+    }
+}
+
+impl Command {
+    fn interpolate(&mut self, vars: &mut Variables) {
+        self.command = vars.interpolate(&self.command);
+        for arg in self.args.iter_mut() {
+            *arg = vars.interpolate(arg);
+        }
+        self.cwd = self.cwd.as_ref().map(|cwd| vars.interpolate(cwd));
+        self.parallel
+            .iter_mut()
+            .for_each(|command| command.interpolate(vars));
+    }
+}
+
+impl Pane {
+    fn interpolate(&mut self, vars: &mut Variables) {
+        self.name = self.name.as_ref().map(|name| vars.interpolate(name));
+        self.path = vars.interpolate(&self.path);
+        self.commands
+            .iter_mut()
+            .for_each(|command| command.interpolate(vars));
+        self.env
+            .values_mut()
+            .for_each(|value| *value = vars.interpolate(value));
+        self.panes
+            .iter_mut()
+            .for_each(|pane| pane.interpolate(vars));
+    }
+}
+
+impl Window {
+    fn interpolate(&mut self, vars: &mut Variables) {
+        self.name = vars.interpolate(&self.name);
+        self.path = vars.interpolate(&self.path);
+        self.pane_commands
+            .iter_mut()
+            .chain(self.startup.iter_mut())
+            .chain(self.shutdown.iter_mut())
+            .for_each(|command| command.interpolate(vars));
+        self.env
+            .values_mut()
+            .for_each(|value| *value = vars.interpolate(value));
+        self.panes
+            .iter_mut()
+            .for_each(|pane| pane.interpolate(vars));
+    }
+}
+
+impl Session {
+    /// Resolves variables in place. Values passed in take precedence over the
+    /// `vars` declared in the config, which in turn take precedence over the environment.
+    /// The values passed in end up in `vars`.
+    pub(crate) fn interpolate(&mut self, overrides: &HashMap<String, String>) -> Vec<String> {
+        let mut values = self.vars.clone();
+        values.extend(overrides.clone());
+        let mut vars = Variables::new(&values);
+
+        self.name = vars.interpolate(&self.name);
+        self.path = vars.interpolate(&self.path);
+        self.startup
+            .iter_mut()
+            .chain(self.shutdown.iter_mut())
+            .chain(self.pre_start.iter_mut())
+            .chain(self.post_start.iter_mut())
+            .chain(self.on_attach.iter_mut())
+            .chain(self.on_detach.iter_mut())
+            .for_each(|command| command.interpolate(&mut vars));
+        self.env
+            .values_mut()
+            .for_each(|value| *value = vars.interpolate(value));
+        self.windows
+            .iter_mut()
+            .for_each(|window| window.interpolate(&mut vars));
+
+        let unresolved = vars.unresolved();
+        // kept for stopping the session, which reloads the config
+        self.vars = values;
+        unresolved
+    }
+}
+
+/// One problem per unresolved variable, labeled wherever the config uses it.
+pub(crate) fn unresolved_problems(config: &Value, unresolved: &[String]) -> Vec<Problem> {
+    let mut uses = vec![];
+    variable_uses(config, "", &mut uses);
+    unresolved
+        .iter()
+        .map(|name| {
+            let paths: Vec<String> = uses
+                .iter()
+                .filter(|(used, _)| used == name)
+                .map(|(_, path)| path.clone())
+                .collect();
+            Problem::new(
+                paths.first().map_or("", String::as_str),
+                format!("Variable '{}' is not set", name),
+                "unresolved",
+            )
+            .at(paths)
+            .with_help(format!(
+                "Declare it in `vars`, pass it with `--var {}=VALUE` or set it in the environment.",
+                name
+            ))
+        })
+        .collect()
+}
+
+// Names of the variables used in the strings of a config, with their paths.
+fn variable_uses(value: &Value, path: &str, uses: &mut Vec<(String, String)>) {
+    match value {
+        Value::String(string) => uses.extend(
+            VARIABLE
+                .captures_iter(string)
+                .filter(|caps| caps.get(1).is_none())
+                .map(|caps| (caps[2].to_string(), path.to_string())),
+        ),
+        Value::Sequence(values) => {
+            for (idx, value) in values.iter().enumerate() {
+                variable_uses(value, &format!("{}[{}]", path, idx), uses);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                match (path, key.as_str()) {
+                    // the values of vars are not interpolated
+                    (_, None) | ("", Some("vars")) => {}
+                    ("", Some(key)) => variable_uses(value, key, uses),
+                    (path, Some(key)) => variable_uses(value, &format!("{}.{}", path, key), uses),
+                }
+            }
+        }
+        _ => {}
+    }
+}
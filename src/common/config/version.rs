@@ -0,0 +1,78 @@
+use std::mem::take;
+
+use miette::{bail, miette, Result};
+use serde_yaml::{Mapping, Value};
+
+/// Version of the config format, configs without a `version` are version 0.
+pub(crate) const VERSION: u32 = 1;
+
+const KEY: &str = "version";
+
+/// Upgrades a session config by one version, returning the deprecated shapes it replaced.
+type Migration = fn(&mut Mapping) -> Result<Vec<String>>;
+
+// MIGRATIONS[n] upgrades version n to n + 1.
+const MIGRATIONS: [Migration; VERSION as usize] = [v0_startup];
+
+// Version of the config format of a session config.
+fn version(value: &Value) -> Result<u32> {
+    match value.get(KEY) {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| miette!("'{}' has to be a positive number", KEY)),
+    }
+}
+
+/// Upgrades a session config to the current [`VERSION`] in place, returning
+/// the deprecated shapes it replaced.
+pub(crate) fn migrate(value: &mut Value) -> Result<Vec<String>> {
+    let version = version(value)?;
+    if version > VERSION {
+        bail!(
+            "Config version {} is newer than version {} supported by this laio, please update laio.",
+            version,
+            VERSION
+        );
+    }
+    let Some(session) = value.as_mapping_mut() else {
+        return Ok(vec![]);
+    };
+
+    let mut deprecations = vec![];
+    for migration in &MIGRATIONS[version as usize..] {
+        deprecations.extend(migration(session)?);
+    }
+
+    // the version goes first, like in the config template
+    let rest = take(session);
+    session.insert(KEY.into(), VERSION.into());
+    session.extend(rest.into_iter().filter(|(key, _)| key != KEY));
+    Ok(deprecations)
+}
+
+/// `commands` of a session became `startup`.
+fn v0_startup(session: &mut Mapping) -> Result<Vec<String>> {
+    if !session.contains_key("commands") {
+        return Ok(vec![]);
+    }
+    if session.contains_key("startup") {
+        bail!("'commands' is a deprecated name of 'startup', use only 'startup'.");
+    }
+    rename(session, "commands", "startup");
+    Ok(vec![
+        "'commands' is deprecated, use 'startup' instead".to_string()
+    ])
+}
+
+// Renames a key without moving it.
+fn rename(mapping: &mut Mapping, from: &str, to: &str) {
+    *mapping = take(mapping)
+        .into_iter()
+        .map(|(key, value)| match key == from {
+            true => (to.into(), value),
+            false => (key, value),
+        })
+        .collect();
+}
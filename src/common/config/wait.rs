@@ -0,0 +1,136 @@
+use std::{
+    fmt::Display,
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    path::Path,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use miette::{bail, miette, Result};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::common::path::sanitize_path;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A readiness gate, all given checks have to pass before the pane or command is started.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, JsonSchema)]
+pub(crate) struct WaitFor {
+    /// TCP port on localhost that has to accept connections.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) port: Option<u16>,
+    /// File that has to exist, relative to the session path or absolute.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) file: Option<String>,
+    /// Name of the pane whose output has to match `pattern`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) pane: Option<String>,
+    /// Regular expression matched against the output of `pane`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) pattern: Option<String>,
+    /// Seconds to wait before giving up.
+    #[serde(default = "timeout", skip_serializing_if = "is_default_timeout")]
+    pub(crate) timeout: u64,
+    /// What to do when the checks did not pass in time.
+    #[serde(default, skip_serializing_if = "OnTimeout::is_default")]
+    pub(crate) on_timeout: OnTimeout,
+}
+
+/// Failure policy of a readiness gate.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OnTimeout {
+    /// Abort starting the session.
+    #[default]
+    Fail,
+    /// Log a warning and start anyway.
+    Continue,
+}
+
+impl OnTimeout {
+    fn is_default(&self) -> bool {
+        *self == OnTimeout::Fail
+    }
+}
+
+fn timeout() -> u64 {
+    30
+}
+
+fn is_default_timeout(value: &u64) -> bool {
+    *value == timeout()
+}
+
+impl WaitFor {
+    /// Polls the checks until all of them pass. Relative files are resolved against
+    /// `path`, `output` captures the output of the named pane.
+    pub(crate) fn wait(
+        &self,
+        path: &String,
+        output: &dyn Fn(&str) -> Result<String>,
+    ) -> Result<()> {
+        let pattern = match (&self.pane, &self.pattern) {
+            (Some(pane), Some(pattern)) => Some((
+                pane,
+                Regex::new(pattern)
+                    .map_err(|e| miette!("Invalid wait_for pattern '{}': {}", pattern, e))?,
+            )),
+            (None, None) => None,
+            _ => bail!("wait_for needs both `pane` and `pattern` to match pane output"),
+        };
+        let file = self.file.as_ref().map(|file| sanitize_path(file, path));
+
+        log::info!("Waiting for {}", self);
+        let deadline = Instant::now() + Duration::from_secs(self.timeout);
+        loop {
+            let ready = self.port.is_none_or(port_open)
+                && file.as_deref().is_none_or(|file| Path::new(file).exists())
+                && match &pattern {
+                    Some((pane, re)) => re.is_match(&output(pane)?),
+                    None => true,
+                };
+            if ready {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return match self.on_timeout {
+                    OnTimeout::Fail => {
+                        bail!("Timed out after {}s waiting for {}", self.timeout, self)
+                    }
+                    OnTimeout::Continue => {
+                        log::warn!("Timed out waiting for {}, continuing", self);
+                        Ok(())
+                    }
+                };
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+impl Display for WaitFor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut checks = vec![];
+        if let Some(port) = self.port {
+            checks.push(format!("port {}", port));
+        }
+        if let Some(file) = &self.file {
+            checks.push(format!("file {}", file));
+        }
+        if let (Some(pane), Some(pattern)) = (&self.pane, &self.pattern) {
+            checks.push(format!("'{}' in pane {}", pattern, pane));
+        }
+        write!(f, "{}", checks.join(", "))
+    }
+}
+
+fn port_open(port: u16) -> bool {
+    TcpStream::connect_timeout(
+        &SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+        POLL_INTERVAL,
+    )
+    .is_ok()
+}
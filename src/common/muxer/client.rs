@@ -1,18 +1,46 @@
-use std::env::{self, current_dir};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env::{self, current_dir},
+    thread::{self, sleep},
+    time::Duration,
+};
 
 use miette::Result;
 use miette::{miette, IntoDiagnostic};
 
+use crate::app::manager::session::manager::{LAIO_CONFIG, LAIO_VARS};
 use crate::cmd_verbose;
-use crate::common::cmd::Runner;
 use crate::common::cmd::Type;
-use crate::common::config::Command;
-use crate::common::path::to_absolute_path;
+use crate::common::cmd::{Runner, Supervision};
+use crate::common::config::{Command, Session, Window};
+use crate::common::path::{sanitize_path, to_absolute_path};
 
 pub(crate) trait Client<R: Runner> {
     fn get_runner(&self) -> &R;
 
-    fn run_commands(&self, commands: &[Command], cwd: &String) -> Result<()> {
+    /// Runs the startup commands, printing which of them ran, failed or were skipped.
+    fn run_startup_commands(&self, session: &Session) -> Result<()> {
+        self.run_steps(&session.startup, &session.path, &session.env, true)
+    }
+
+    fn run_commands(
+        &self,
+        commands: &[Command],
+        cwd: &String,
+        env: &HashMap<String, String>,
+    ) -> Result<()> {
+        self.run_steps(commands, cwd, env, false)
+    }
+
+    /// Runs a list of commands, printing their [`summary`] when `print_summary`
+    /// is set and logging it otherwise.
+    fn run_steps(
+        &self,
+        commands: &[Command],
+        cwd: &String,
+        env: &HashMap<String, String>,
+        print_summary: bool,
+    ) -> Result<()> {
         if commands.is_empty() {
             log::info!("No commands to run...");
             return Ok(());
@@ -25,21 +53,235 @@ pub(crate) trait Client<R: Runner> {
         log::trace!("Current directory: {:?}", current_dir);
         log::trace!("Changing to: {:?}", cwd);
 
-        env::set_current_dir(to_absolute_path(cwd)?)
+        let path = to_absolute_path(cwd)?;
+        env::set_current_dir(&path)
             .map_err(|_| miette!("Unable to change to directory: {:?}", &cwd))?;
+        let path = path.to_string_lossy().to_string();
+
+        let current_env: Vec<(&String, Option<String>)> =
+            env.keys().map(|key| (key, env::var(key).ok())).collect();
 
+        for (key, value) in env {
+            log::trace!("Setting env: {}={}", key, value);
+            env::set_var(key, value);
+        }
+
+        let mut result = Ok(());
+        let mut steps = vec![];
         for cmd in commands {
-            let _res: String = self
-                .get_runner()
-                .run(&cmd_verbose!("{}", cmd.to_string()))
-                .map_err(|_| miette!("Failed to run command: {}", cmd.to_string()))?;
+            let group: Vec<&Command> = if cmd.parallel.is_empty() {
+                vec![cmd]
+            } else {
+                cmd.parallel.iter().collect()
+            };
+            if result.is_err() {
+                steps.extend(group.into_iter().map(|cmd| (cmd, Step::Skipped)));
+                continue;
+            }
+            let outcomes = if cmd.parallel.is_empty() {
+                vec![run_command(self.get_runner(), cmd, &path, None)]
+            } else {
+                run_parallel(self.get_runner(), &cmd.parallel, &path)
+            };
+            for (cmd, outcome) in group.into_iter().zip(outcomes) {
+                match outcome {
+                    Ok(()) => steps.push((cmd, Step::Ran)),
+                    Err(e) if cmd.allow_failure => {
+                        log::warn!("Ignoring failed command: {:?}", e);
+                        steps.push((cmd, Step::Allowed));
+                    }
+                    Err(e) if result.is_err() => {
+                        log::error!("{:?}", e);
+                        steps.push((cmd, Step::Failed));
+                    }
+                    Err(e) => {
+                        steps.push((cmd, Step::Failed));
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        if print_summary {
+            println!("{}", summary(&steps));
+        } else {
+            log::debug!("{}", summary(&steps));
+        }
+
+        for (key, value) in current_env {
+            match value {
+                Some(value) => env::set_var(key, value),
+                None => env::remove_var(key),
+            }
         }
 
         env::set_current_dir(&current_dir)
             .map_err(|_| miette!("Failed to restore original directory {:?}", current_dir))?;
 
+        result?;
+
         log::info!("Completed commands.");
 
         Ok(())
     }
+
+    /// Runs window hooks in the window path with the session and window env.
+    fn run_window_commands(
+        &self,
+        commands: &[Command],
+        session: &Session,
+        window: &Window,
+    ) -> Result<()> {
+        let mut env = session.env.clone();
+        env.extend(window.env.clone());
+        self.run_commands(commands, &sanitize_path(&window.path, &session.path), &env)
+    }
+
+    /// Runs lifecycle hooks in the session path with the [`hook_env`].
+    fn run_hooks(&self, commands: &[Command], session: &Session, config: &str) -> Result<()> {
+        self.run_commands(commands, &session.path, &hook_env(session, config))
+    }
+
+    /// Runs the shutdown commands of the windows in reverse order, then the session ones.
+    fn run_shutdown_commands(&self, session: &Session) -> Result<()> {
+        session
+            .active_windows()
+            .into_iter()
+            .rev()
+            .try_for_each(|window| self.run_window_commands(&window.shutdown, session, window))?;
+        self.run_commands(&session.shutdown, &session.path, &session.env)
+    }
+}
+
+/// Runs a single command in `path`, waiting for its gate and retrying it on failure.
+/// Commands running next to others get their output prefixed with `prefix`.
+fn run_command<R: Runner>(
+    runner: &R,
+    cmd: &Command,
+    path: &String,
+    prefix: Option<String>,
+) -> Result<()> {
+    if let Some(wait_for) = &cmd.wait_for {
+        wait_for.wait(path, &|pane| {
+            Err(miette!(
+                "Waiting for the output of pane '{}' is only supported in panes",
+                pane
+            ))
+        })?;
+    }
+
+    // commands of a parallel group share the working directory of the process
+    let command = match &cmd.cwd {
+        Some(cwd) => format!("cd {} && {}", shell_quote(&sanitize_path(cwd, path)), cmd),
+        None => cmd.to_string(),
+    };
+    let runnable = match (cmd.timeout, prefix) {
+        (None, None) => cmd_verbose!("{}", command),
+        (timeout, prefix) => Type::Supervised(
+            command,
+            Supervision {
+                timeout: timeout.map(Duration::from_secs),
+                prefix,
+            },
+        ),
+    };
+    let mut result: Result<String> = runner.run(&runnable);
+    for attempt in 1..=cmd.retries {
+        let Err(e) = &result else {
+            break;
+        };
+        log::warn!("{}, retrying ({}/{})", e, attempt, cmd.retries);
+        result = runner.run(&runnable);
+    }
+    result?;
+
+    if cmd.delay_ms > 0 {
+        sleep(Duration::from_millis(cmd.delay_ms));
+    }
+    Ok(())
+}
+
+/// Runs a parallel group, returning the outcome of each command once all of them are done.
+fn run_parallel<R: Runner>(runner: &R, group: &[Command], path: &String) -> Vec<Result<()>> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = group
+            .iter()
+            .enumerate()
+            .map(|(idx, cmd)| {
+                let prefix = format!("{}:{}", idx + 1, cmd.command);
+                scope.spawn(move || run_command(runner, cmd, path, Some(prefix)))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .zip(group)
+            .map(|(handle, cmd)| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(miette!("Failed to run command: {}", cmd)))
+            })
+            .collect()
+    })
+}
+
+/// What became of a command in [`Client::run_commands`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Step {
+    Ran,
+    Failed,
+    /// Failed, but the command has `allow_failure` set.
+    Allowed,
+    /// Not run, because a command before it failed.
+    Skipped,
+}
+
+pub(crate) fn summary(steps: &[(&Command, Step)]) -> String {
+    steps
+        .iter()
+        .map(|(cmd, step)| match step {
+            Step::Ran => format!("✔ {}", cmd),
+            Step::Failed => format!("✘ {}", cmd),
+            Step::Allowed => format!("✘ {} (allowed to fail)", cmd),
+            Step::Skipped => format!("- {} (skipped)", cmd),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Environment of lifecycle hooks, the session env plus `LAIO_SESSION`,
+/// `LAIO_CONFIG` and `LAIO_PATH`.
+pub(crate) fn hook_env(session: &Session, config: &str) -> HashMap<String, String> {
+    let mut env = session.env.clone();
+    env.insert("LAIO_SESSION".to_string(), session.name.clone());
+    env.insert(LAIO_CONFIG.to_string(), config.to_string());
+    env.insert("LAIO_PATH".to_string(), session.path.clone());
+    env
+}
+
+/// Quotes a value as a single shell word.
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// The variables of a session as saved in `LAIO_VARS` when it starts.
+pub(crate) fn save_vars(session: &Session) -> String {
+    serde_json::to_string(&session.vars.iter().collect::<BTreeMap<_, _>>()).unwrap_or_default()
+}
+
+/// Variables a session was started with, read back from `LAIO_VARS`, with
+/// `vars` taking precedence.
+pub(crate) fn saved_vars(
+    saved: Result<String>,
+    vars: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut saved: HashMap<String, String> = match saved {
+        Ok(saved) if !saved.trim().is_empty() => {
+            serde_json::from_str(saved.trim()).unwrap_or_else(|e| {
+                log::warn!("Ignoring malformed {}: {}", LAIO_VARS, e);
+                HashMap::new()
+            })
+        }
+        _ => HashMap::new(),
+    };
+    saved.extend(vars.clone());
+    saved
 }
@@ -1,3 +1,5 @@
+use std::{collections::HashMap, path::Path};
+
 use miette::Result;
 
 use crate::common::config::Session;
@@ -9,7 +11,14 @@ pub(crate) trait Multiplexer {
         skip_attach: bool,
         skip_cmds: bool,
     ) -> Result<()>;
-    fn stop(&self, name: &Option<String>, skip_cmds: bool, stop_all: bool) -> Result<()>;
+    fn stop(
+        &self,
+        name: &Option<String>,
+        config_dir: &Path,
+        vars: &HashMap<String, String>,
+        skip_cmds: bool,
+        stop_all: bool,
+    ) -> Result<()>;
     fn list_sessions(&self) -> Result<Vec<String>>;
     fn switch(&self, name: &str, skip_attach: bool) -> Result<bool>;
     fn get_session(&self) -> Result<Session>;
@@ -1,8 +1,13 @@
-use crate::common::config::Session;
+use std::{collections::HashMap, path::Path};
+
+use crate::common::config::{Command, Session};
 use miette::Result;
 use mockall::mock;
 
-use super::Multiplexer;
+use super::{
+    client::{summary, Step},
+    Multiplexer,
+};
 
 mock! {
     pub Multiplexer {}
@@ -19,6 +24,8 @@ mock! {
         fn stop(
             &self,
             name: &Option<String>,
+            config_dir: &Path,
+            vars: &HashMap<String, String>,
             skip_cmds: bool,
             stop_all: bool,
         ) -> Result<()>;
@@ -34,3 +41,21 @@ mock! {
         fn get_session(&self) -> Result<Session>;
     }
 }
+
+#[test]
+fn client_summary() {
+    let ran = Command::from_string("docker compose up");
+    let allowed = Command::from_string("lint");
+    let failed = Command::from_string("make");
+    let skipped = Command::from_string("notify-send done");
+
+    assert_eq!(
+        summary(&[
+            (&ran, Step::Ran),
+            (&allowed, Step::Allowed),
+            (&failed, Step::Failed),
+            (&skipped, Step::Skipped),
+        ]),
+        "✔ docker compose up\n✘ lint (allowed to fail)\n✘ make\n- notify-send done (skipped)"
+    );
+}
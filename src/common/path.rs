@@ -7,6 +7,8 @@ use std::{
 
 use miette::{bail, miette, Error, IntoDiagnostic, Result};
 
+use crate::common::config::format::with_extension;
+
 pub(crate) fn current_working_path() -> Result<PathBuf> {
     let current_dir = env::current_dir().into_diagnostic()?;
     let home_dir = home_dir()?;
@@ -97,9 +99,9 @@ pub(crate) fn find_config(config_path: &Path) -> Result<PathBuf> {
             filename: &OsString,
             home: &PathBuf,
         ) -> Result<PathBuf> {
-            let file_path = current_path.join(filename);
+            let file_path = with_extension(&current_path.join(filename));
 
-            if file_path.exists() {
+            if file_path.is_file() {
                 log::info!("Found config: {:?}", file_path);
                 return Ok(file_path);
             }
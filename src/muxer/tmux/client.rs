@@ -3,7 +3,7 @@ use miette::{bail, miette, IntoDiagnostic, Result};
 use serde::Deserialize;
 use std::{
     cell::RefCell,
-    collections::{HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
     fmt::Debug,
     path::{Path, PathBuf},
     process,
@@ -17,7 +17,7 @@ use crate::{
     common::{
         cmd::{Runner, Type},
         config::Command,
-        muxer::Client,
+        muxer::{client::shell_quote, Client},
     },
 };
 
@@ -49,11 +49,17 @@ impl<R: Runner> TmuxClient<R> {
         }
     }
 
-    pub(crate) fn create_session(&self, session_name: &str, session_path: &str) -> Result<()> {
+    pub(crate) fn create_session(
+        &self,
+        session_name: &str,
+        session_path: &str,
+        env: &HashMap<String, String>,
+    ) -> Result<()> {
         let _: () = self.cmd_runner.run(&cmd_basic!(
-            "tmux new-session -d -s \"{}\" -c \"{}\"",
+            "tmux new-session -d -s \"{}\" -c \"{}\"{}",
             session_name,
             session_path,
+            env_args(env)
         ))?;
 
         Ok(())
@@ -88,12 +94,12 @@ impl<R: Runner> TmuxClient<R> {
     }
 
     pub(crate) fn stop_session(&self, name: &str) -> Result<()> {
-        self.session_exists(name)
-            .then(|| {
-                self.cmd_runner
-                    .run(&cmd_basic!("tmux kill-session -t \"{}\"", name))
-            })
-            .unwrap_or(Ok(()))
+        if self.session_exists(name) {
+            self.cmd_runner
+                .run(&cmd_basic!("tmux kill-session -t \"{}\"", name))
+        } else {
+            Ok(())
+        }
     }
 
     pub(crate) fn new_window(
@@ -101,12 +107,14 @@ impl<R: Runner> TmuxClient<R> {
         session_name: &str,
         window_name: &str,
         path: &str,
+        env: &HashMap<String, String>,
     ) -> Result<String> {
         self.cmd_runner.run(&cmd_basic!(
-            "tmux new-window -Pd -t \"{}\" -n \"{}\" -c \"{}\" -F \"#{{window_id}}\"",
+            "tmux new-window -Pd -t \"{}\" -n \"{}\" -c \"{}\"{} -F \"#{{window_id}}\"",
             session_name,
             window_name,
-            path
+            path,
+            env_args(env)
         ))
     }
 
@@ -117,11 +125,41 @@ impl<R: Runner> TmuxClient<R> {
         ))
     }
 
-    pub(crate) fn split_window(&self, target: &Target, path: &str) -> Result<String> {
+    pub(crate) fn split_window(
+        &self,
+        target: &Target,
+        path: &str,
+        env: &HashMap<String, String>,
+        process: Option<&str>,
+    ) -> Result<String> {
+        self.cmd_runner.run(&cmd_basic!(
+            "tmux split-window -t {} -c \"{}\"{} -P -F \"#{{pane_id}}\"{}",
+            target,
+            path,
+            env_args(env),
+            process
+                .map(|process| format!(" {}", shell_quote(process)))
+                .unwrap_or_default()
+        ))
+    }
+
+    /// Replaces the process of a pane, e.g. the shell of the pane a window starts with,
+    /// restarting its default command without a `process`.
+    pub(crate) fn respawn_pane(
+        &self,
+        target: &Target,
+        path: &str,
+        env: &HashMap<String, String>,
+        process: Option<&str>,
+    ) -> Result<()> {
         self.cmd_runner.run(&cmd_basic!(
-            "tmux split-window -t {} -c \"{}\" -P -F \"#{{pane_id}}\"",
+            "tmux respawn-pane -k -t {} -c \"{}\"{}{}",
             target,
-            path
+            path,
+            env_args(env),
+            process
+                .map(|process| format!(" {}", shell_quote(process)))
+                .unwrap_or_default()
         ))
     }
 
@@ -132,10 +170,10 @@ impl<R: Runner> TmuxClient<R> {
 
     pub(crate) fn setenv(&self, target: &Target, name: &str, value: &str) {
         self.cmds.borrow_mut().push_back(cmd_basic!(
-            "tmux setenv -t {} {} \"{}\"",
+            "tmux setenv -t {} {} {}",
             target,
             name,
-            value
+            shell_quote(value)
         ))
     }
 
@@ -150,20 +188,60 @@ impl<R: Runner> TmuxClient<R> {
             .ok_or_else(|| miette!("Variable not found or malformed output"))
     }
 
-    pub(crate) fn register_commands(&self, target: &Target, cmds: &Vec<Command>) {
+    pub(crate) fn register_commands(&self, target: &Target, cmds: &[Command]) {
         for cmd in cmds {
-            self.register_command(target, &cmd.to_string())
+            self.register_command(target, cmd)
         }
     }
 
-    pub(crate) fn register_command(&self, target: &Target, cmd: &String) {
-        self.cmds
-            .borrow_mut()
-            .push_back(cmd_basic!("tmux send-keys -t {} '{}' C-m", target, cmd,))
+    pub(crate) fn register_command(&self, target: &Target, cmd: &Command) {
+        let keys = shell_quote(&cmd.to_string());
+        let mut cmds = self.cmds.borrow_mut();
+        match (cmd.literal, cmd.enter) {
+            (true, enter) => {
+                cmds.push_back(cmd_basic!("tmux send-keys -l -t {} {}", target, keys));
+                if enter {
+                    cmds.push_back(cmd_basic!("tmux send-keys -t {} C-m", target));
+                }
+            }
+            (false, true) => {
+                cmds.push_back(cmd_basic!("tmux send-keys -t {} {} C-m", target, keys))
+            }
+            (false, false) => cmds.push_back(cmd_basic!("tmux send-keys -t {} {}", target, keys)),
+        }
+        if cmd.delay_ms > 0 {
+            cmds.push_back(cmd_basic!(
+                "sleep {}.{:03}",
+                cmd.delay_ms / 1000,
+                cmd.delay_ms % 1000
+            ));
+        }
     }
 
-    pub(crate) fn zoom_pane(&self, target: &Target) {
-        self.register_command(target, &format!("tmux resize-pane -Z -t {}", target));
+    /// Sends a command to a pane right away instead of queueing it.
+    pub(crate) fn send_command(&self, target: &Target, cmd: &Command) -> Result<()> {
+        self.register_command(target, cmd);
+        self.flush_commands()
+    }
+
+    pub(crate) fn capture_pane(&self, target: &Target) -> Result<String> {
+        self.cmd_runner
+            .run(&cmd_basic!("tmux capture-pane -p -J -S - -t {}", target))
+    }
+
+    pub(crate) fn zoom_pane(&self, target: &Target) -> Result<()> {
+        self.cmd_runner
+            .run(&cmd_basic!("tmux resize-pane -Z -t {}", target))
+    }
+
+    pub(crate) fn select_pane(&self, target: &Target) -> Result<()> {
+        self.cmd_runner
+            .run(&cmd_basic!("tmux select-pane -t {}", target))
+    }
+
+    pub(crate) fn select_window(&self, target: &Target) -> Result<()> {
+        self.cmd_runner
+            .run(&cmd_basic!("tmux select-window -t {}", target))
     }
 
     pub(crate) fn flush_commands(&self) -> Result<()> {
@@ -236,6 +314,23 @@ impl<R: Runner> TmuxClient<R> {
             .run(&cmd_basic!("tmux select-pane -t {} -P '{}'", target, style))
     }
 
+    /// Sets a session hook running `script` with `run-shell` in the background.
+    pub(crate) fn set_hook(&self, target: &Target, hook: &str, script: &str) -> Result<()> {
+        let run_shell = format!(
+            "run-shell -b \"{}\"",
+            script
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('$', "\\$")
+        );
+        self.cmd_runner.run(&cmd_basic!(
+            "tmux set-hook -t {} {} {}",
+            target,
+            hook,
+            shell_quote(&run_shell)
+        ))
+    }
+
     pub(crate) fn bind_key(&self, key: &str, cmd: &str) -> Result<()> {
         self.cmd_runner
             .run(&cmd_basic!("tmux bind-key -T {} {}", &key, &cmd))
@@ -397,10 +492,19 @@ impl<R: Runner> TmuxClient<R> {
         Ok(pane_map)
     }
 
-    pub(crate) fn set_pane_title(&self, target: &Target, title: &str) {
-        self.register_command(
+    pub(crate) fn rename_pane(&self, target: &Target, title: &str) -> Result<()> {
+        self.cmd_runner.run(&cmd_basic!(
+            "tmux select-pane -t {} -T \"{}\"",
             target,
-            &format!("tmux select-pane -t {} -T {} ", target, title),
-        );
+            title
+        ))
     }
 }
+
+fn env_args(env: &HashMap<String, String>) -> String {
+    env.iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(key, value)| format!(" -e {}", shell_quote(&format!("{}={}", key, value))))
+        .collect()
+}
@@ -0,0 +1,39 @@
+use crate::common::config::{Cell, FlexDirection, Rect};
+
+impl Cell {
+    /// The tmux layout of the cell without checksum, `ids` are the pane ids of
+    /// the leaves in order.
+    pub(crate) fn layout(&self, ids: &mut impl Iterator<Item = String>) -> String {
+        let Rect {
+            width,
+            height,
+            x,
+            y,
+        } = self.rect;
+        match self.children.as_slice() {
+            [] => match ids.next() {
+                Some(id) => format!("{}x{},{},{},{}", width, height, x, y, id.replace('%', "")),
+                None => format!("{}x{},{},{}", width, height, x, y),
+            },
+            // a single nested pane takes the place of its parent
+            [child] => child.layout(ids),
+            children => {
+                let (open, close) = match self.direction {
+                    FlexDirection::Column => ('[', ']'),
+                    FlexDirection::Row => ('{', '}'),
+                };
+                let children: Vec<String> = children.iter().map(|c| c.layout(ids)).collect();
+                format!(
+                    "{}x{},{},{}{}{}{}",
+                    width,
+                    height,
+                    x,
+                    y,
+                    open,
+                    children.join(","),
+                    close
+                )
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 pub(crate) mod client;
+pub(crate) mod layout;
 pub(crate) mod mux;
 pub(crate) mod parser;
+pub(crate) mod preview;
 pub(crate) mod target;
 
 pub(crate) use client::Dimensions;
@@ -1,13 +1,21 @@
-use std::rc::Rc;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    rc::Rc,
+};
 
 use miette::{bail, Result};
 
 use crate::{
-    app::manager::session::manager::LAIO_CONFIG,
+    app::manager::session::manager::{LAIO_CONFIG, LAIO_VARS},
     common::{
         cmd::{Runner, ShellRunner},
-        config::{FlexDirection, Pane, Session},
-        muxer::{Client, Multiplexer},
+        config::{Cell, Command, Pane, Session, WaitFor},
+        muxer::{
+            client::{hook_env, save_vars, saved_vars, shell_quote},
+            Client, Multiplexer,
+        },
         path::{home_dir, resolve_symlink, sanitize_path, to_absolute_path},
     },
     muxer::tmux::parser::parse,
@@ -16,24 +24,43 @@ use crate::{
 
 use super::{client::TmuxClient, Dimensions, Target};
 
-struct LayoutInfo<'a> {
-    dimensions: &'a Dimensions,
-    direction: &'a FlexDirection,
-    xy: (usize, usize),
-}
-
 struct LayoutMeta<'a> {
     id: &'a str,
     name: &'a str,
     path: &'a str,
+    env: &'a HashMap<String, String>,
+    // whether the first pane was created with its env, unlike the one of new-session
+    first_pane_env: bool,
+    pane_commands: &'a [Command],
+    selected: &'a RefCell<SelectedPanes>,
+    gates: &'a RefCell<Gates>,
+}
+
+// Ids of the panes to focus and zoom once the layout of a window is applied.
+#[derive(Default)]
+struct SelectedPanes {
+    focus: Option<String>,
+    zoom: Option<String>,
+}
+
+// Panes whose commands are sent once their readiness gates pass, and the
+// window and pane ids of named panes whose output may be waited for.
+#[derive(Default)]
+struct Gates {
+    named: HashMap<String, (String, String)>,
+    deferred: Vec<GatedPane>,
 }
 
-struct CalculateInfo {
-    depth: usize,
-    dividers: usize,
-    flex: usize,
-    flex_total: usize,
-    index: usize,
+struct GatedPane {
+    name: Option<String>,
+    window_id: String,
+    pane_id: String,
+    wait_for: Option<WaitFor>,
+    commands: Vec<Command>,
+    // set for panes that exec their commands, which replace the pane process
+    process: Option<String>,
+    path: String,
+    env: HashMap<String, String>,
 }
 
 pub(crate) struct Tmux<R: Runner = ShellRunner> {
@@ -58,234 +85,179 @@ impl<R: Runner> Tmux<R> {
         session: &Session,
         dimensions: &Dimensions,
         skip_cmds: bool,
-    ) -> Result<()> {
+        gates: &RefCell<Gates>,
+    ) -> Result<Option<String>> {
         let base_idx = self.client.get_base_idx()?;
         log::trace!("base-index: {}", base_idx);
 
+        let mut focused_window = None;
         session
-            .windows
-            .iter()
+            .active_windows()
+            .into_iter()
             .enumerate()
             .try_for_each(|(i, window)| -> Result<()> {
                 let idx = i + base_idx;
+                let window_path = sanitize_path(&window.path, &session.path);
+
+                if !skip_cmds {
+                    self.client
+                        .run_window_commands(&window.startup, session, window)?;
+                }
 
+                let path = sanitize_path(
+                    window.first_leaf_path().unwrap_or(&"".to_string()),
+                    &window_path,
+                );
                 let window_id = if idx == base_idx {
                     let id = self.client.get_current_window(&session.name)?;
                     self.client
                         .rename_window(&tmux_target!(&session.name, &id), &window.name)?;
                     id
                 } else {
-                    let path = sanitize_path(
-                        window.first_leaf_path().unwrap_or(&"".to_string()),
-                        &session.path,
-                    );
-
-                    self.client.new_window(&session.name, &window.name, &path)?
+                    self.client.new_window(
+                        &session.name,
+                        &window.name,
+                        &path,
+                        &window.first_leaf_env(),
+                    )?
                 };
                 log::trace!("window-id: {}", window_id);
 
-                self.client.select_custom_layout(
-                    &tmux_target!(&session.name, &window_id),
-                    &self.generate_layout(
-                        &LayoutMeta {
-                            name: session.name.as_str(),
-                            id: window_id.as_str(),
-                            path: session.path.as_str(),
-                        },
-                        &LayoutInfo {
-                            dimensions,
-                            direction: &window.flex_direction,
-                            xy: (0, 0),
-                        },
-                        &window.panes,
-                        skip_cmds,
-                        0,
-                    )?,
+                let selected = RefCell::new(SelectedPanes::default());
+                let (direction, panes) = window.flex_panes();
+                let pane_ids = self.generate_layout(
+                    &LayoutMeta {
+                        name: session.name.as_str(),
+                        id: window_id.as_str(),
+                        path: window_path.as_str(),
+                        env: &window.env,
+                        first_pane_env: idx != base_idx,
+                        pane_commands: &window.pane_commands,
+                        selected: &selected,
+                        gates,
+                    },
+                    &panes,
+                    skip_cmds,
                 )?;
+                let target = tmux_target!(&session.name, &window_id);
+                match &window.layout {
+                    Some(preset) => self.client.select_layout(&target, &preset.to_string())?,
+                    None => match Cell::solve(
+                        &panes,
+                        &direction,
+                        dimensions.width,
+                        dimensions.height,
+                    ) {
+                        Some(cell) => self
+                            .client
+                            .select_custom_layout(&target, &cell.layout(&mut pane_ids.into_iter()))?,
+                        None => log::warn!(
+                            "window '{}' is too small for its panes at {}x{}, keeping the tiled layout",
+                            window.name,
+                            dimensions.width,
+                            dimensions.height
+                        ),
+                    },
+                }
 
-                Ok(())
-            })
-    }
-
-    fn calculate_pane_dimensions(
-        &self,
-        layout_info: &LayoutInfo,
-        calculate_info: &CalculateInfo,
-        panes: &[Pane],
-    ) -> Option<(usize, usize, usize, usize)> {
-        let (current_x, current_y) = layout_info.xy;
-        let index = calculate_info.index;
-        let (pane_width, pane_height, next_x, next_y) = match layout_info.direction {
-            FlexDirection::Column => {
-                let h = self.calculate_dimension(
-                    calculate_info,
-                    index == panes.len() - 1,
-                    current_y,
-                    layout_info.dimensions.height,
-                )?;
-                (
-                    layout_info.dimensions.width,
-                    h,
-                    layout_info.xy.0,
-                    layout_info.xy.1 + h + 1,
-                )
-            }
-            _ => {
-                let w = self.calculate_dimension(
-                    calculate_info,
-                    index == panes.len() - 1,
-                    current_x,
-                    layout_info.dimensions.width,
-                )?;
-                (
-                    w,
-                    layout_info.dimensions.height,
-                    current_x + w + 1,
-                    current_y,
-                )
-            }
-        };
-        Some((pane_width, pane_height, next_x, next_y))
-    }
+                // zooming last keeps the zoomed pane visible over the focused one
+                let selected = selected.into_inner();
+                if let Some(pane_id) = selected.focus {
+                    self.client
+                        .select_pane(&tmux_target!(&session.name, &window_id, &pane_id))?;
+                }
+                if let Some(pane_id) = selected.zoom {
+                    self.client
+                        .zoom_pane(&tmux_target!(&session.name, &window_id, &pane_id))?;
+                }
 
-    fn calculate_dimension(
-        &self,
-        calculate_info: &CalculateInfo,
-        is_last_pane: bool,
-        current_value: usize,
-        total_value: usize,
-    ) -> Option<usize> {
-        let (flex, flex_total, dividers, depth, index) = (
-            calculate_info.flex,
-            calculate_info.flex_total,
-            calculate_info.dividers,
-            calculate_info.depth,
-            calculate_info.index,
-        );
-        if is_last_pane {
-            log::trace!(
-                "current_value: {}, total_value: {}",
-                current_value,
-                total_value
-            );
-            if current_value >= total_value {
-                log::warn!(
-                    "skipping pane: total_value: {}, current_value: {}",
-                    total_value,
-                    current_value
-                );
-                return None;
-            }
-            Some(total_value - current_value)
-        } else {
-            Some(if depth > 0 || index > 0 {
-                total_value * flex / flex_total - dividers
-            } else {
-                total_value * flex / flex_total
-            })
-        }
-    }
+                if window.focus {
+                    focused_window = Some(window_id);
+                }
+                Ok(())
+            })?;
 
-    fn generate_pane_string(
-        &self,
-        layout_meta: &LayoutMeta,
-        layout_info: &LayoutInfo,
-        pane: &Pane,
-        depth: usize,
-        pane_id: &str,
-        skip_cmds: bool,
-    ) -> Result<String> {
-        let pane_string = if !pane.panes.is_empty() {
-            self.generate_layout(
-                layout_meta,
-                &LayoutInfo {
-                    dimensions: layout_info.dimensions,
-                    direction: &pane.flex_direction,
-                    xy: layout_info.xy,
-                },
-                &pane.panes,
-                skip_cmds,
-                depth + 1,
-            )?
-        } else {
-            let (current_x, current_y) = layout_info.xy;
-            format!(
-                "{0}x{1},{2},{3},{4}",
-                layout_info.dimensions.width,
-                layout_info.dimensions.height,
-                current_x,
-                current_y,
-                pane_id.replace('%', "")
-            )
-        };
-        Ok(pane_string)
+        Ok(focused_window)
     }
 
+    // Creates the panes of a window, returning the ids of the innermost panes
+    // in layout order.
     fn generate_layout(
         &self,
         layout_meta: &LayoutMeta,
-        layout_info: &LayoutInfo,
         panes: &[Pane],
         skip_cmds: bool,
-        depth: usize,
-    ) -> Result<String> {
-        let flex_total = panes.iter().map(|p| p.flex).sum();
-
-        let (mut current_x, mut current_y) = layout_info.xy;
-
-        let mut pane_strings: Vec<String> = Vec::new();
-        let mut num_dividers = 0;
+    ) -> Result<Vec<String>> {
+        let mut pane_ids: Vec<String> = Vec::new();
 
         let session_name = layout_meta.name;
         let window_path = layout_meta.path;
         let window_id = layout_meta.id;
 
         for (index, pane) in panes.iter().enumerate() {
-            let (pane_width, pane_height, next_x, next_y) = match self.calculate_pane_dimensions(
-                &LayoutInfo {
-                    dimensions: layout_info.dimensions,
-                    direction: layout_info.direction,
-                    xy: (current_x, current_y),
-                },
-                &CalculateInfo {
-                    depth,
-                    dividers: num_dividers,
-                    flex: pane.flex,
-                    index,
-                    flex_total,
-                },
-                panes,
-            ) {
-                Some(value) => value,
-                None => continue,
-            };
-
-            if depth > 0 || index > 0 {
-                num_dividers += 1;
+            let is_leaf = pane.active_panes().is_empty();
+            let commands: Vec<Command> = if is_leaf {
+                layout_meta.pane_commands.to_vec()
+            } else {
+                vec![]
             }
+            .into_iter()
+            .chain(pane.commands.iter().cloned())
+            .collect();
+            let gated = pane.wait_for.is_some() || commands.iter().any(|c| c.wait_for.is_some());
+            let exec = is_leaf && pane.exec && !skip_cmds;
+            let process = is_leaf
+                .then(|| pane_process(pane, &commands, exec && !gated))
+                .flatten();
 
             let pane_id = if index > 0 {
                 let path = sanitize_path(
                     pane.first_leaf_path().unwrap_or(&".".to_string()),
                     &window_path.to_string(),
                 );
-                self.client
-                    .split_window(&tmux_target!(session_name, window_id), &path)?
+                let mut env = layout_meta.env.clone();
+                env.extend(pane.first_leaf_env());
+                self.client.split_window(
+                    &tmux_target!(session_name, window_id),
+                    &path,
+                    &env,
+                    process.as_deref(),
+                )?
             } else {
-                self.client
-                    .get_current_pane(&tmux_target!(session_name, window_id))?
+                let pane_id = self
+                    .client
+                    .get_current_pane(&tmux_target!(session_name, window_id))?;
+                let mut env = layout_meta.env.clone();
+                env.extend(pane.env.clone());
+                let missing_env = is_leaf && !layout_meta.first_pane_env && !env.is_empty();
+                if process.is_some() || missing_env {
+                    self.client.respawn_pane(
+                        &tmux_target!(session_name, window_id, pane_id.as_str()),
+                        &sanitize_path(&pane.path, &window_path.to_string()),
+                        &env,
+                        process.as_deref(),
+                    )?;
+                }
+                pane_id
             };
 
-            if pane.name.is_some() {
-                self.client.set_pane_title(
+            if let Some(name) = &pane.name {
+                self.client.rename_pane(
                     &tmux_target!(session_name, window_id, pane_id.as_str()),
-                    pane.name.clone().unwrap().as_str(),
+                    name,
+                )?;
+                layout_meta.gates.borrow_mut().named.insert(
+                    name.to_string(),
+                    (window_id.to_string(), pane_id.to_string()),
                 );
             };
 
             if pane.zoom {
-                self.client
-                    .zoom_pane(&tmux_target!(session_name, window_id, pane_id.as_str()));
+                layout_meta.selected.borrow_mut().zoom = Some(pane_id.clone());
+            };
+
+            if pane.focus {
+                layout_meta.selected.borrow_mut().focus = Some(pane_id.clone());
             };
 
             if let Some(style) = &pane.style {
@@ -298,51 +270,116 @@ impl<R: Runner> Tmux<R> {
             self.client
                 .select_layout(&tmux_target!(session_name, window_id), "tiled")?;
 
-            pane_strings.push(self.generate_pane_string(
-                layout_meta,
-                &LayoutInfo {
-                    dimensions: &Dimensions {
-                        width: pane_width,
-                        height: pane_height,
+            if is_leaf {
+                pane_ids.push(pane_id.clone());
+            } else {
+                let mut env = layout_meta.env.clone();
+                env.extend(pane.env.clone());
+                pane_ids.extend(self.generate_layout(
+                    &LayoutMeta {
+                        env: &env,
+                        ..*layout_meta
                     },
-                    direction: layout_info.direction,
-                    xy: (current_x, current_y),
-                },
-                pane,
-                depth,
-                &pane_id,
-                skip_cmds,
-            )?);
-
-            (current_x, current_y) = (next_x, next_y);
+                    &pane.active_panes(),
+                    skip_cmds,
+                )?);
+            }
+
             if !skip_cmds {
-                self.client.register_commands(
-                    &tmux_target!(session_name, window_id, pane_id.as_str()),
-                    &pane.commands,
-                );
+                if gated {
+                    let mut env = layout_meta.env.clone();
+                    env.extend(pane.env.clone());
+                    layout_meta.gates.borrow_mut().deferred.push(GatedPane {
+                        name: pane.name.clone(),
+                        window_id: window_id.to_string(),
+                        pane_id: pane_id.clone(),
+                        wait_for: pane.wait_for.clone(),
+                        process: exec.then(|| pane_process(pane, &commands, true)).flatten(),
+                        path: sanitize_path(&pane.path, &window_path.to_string()),
+                        env,
+                        commands,
+                    });
+                } else if !exec {
+                    self.client.register_commands(
+                        &tmux_target!(session_name, window_id, pane_id.as_str()),
+                        &commands,
+                    );
+                }
             };
         }
 
-        if pane_strings.len() > 1 {
-            let (open_delimiter, close_delimiter) = match layout_info.direction {
-                FlexDirection::Column => ('[', ']'),
-                FlexDirection::Row => ('{', '}'),
+        Ok(pane_ids)
+    }
+
+    // Starts gated panes after the panes they wait for, waiting for each gate in turn.
+    fn start_gated_panes(&self, session: &Session, gates: Gates) -> Result<()> {
+        let Gates {
+            named,
+            mut deferred,
+        } = gates;
+        let output = |name: &str| -> Result<String> {
+            match named.get(name) {
+                Some((window_id, pane_id)) => {
+                    self.client
+                        .capture_pane(&tmux_target!(&session.name, window_id, pane_id))
+                }
+                None => bail!("Unable to wait for the output of unknown pane '{}'", name),
+            }
+        };
+
+        while !deferred.is_empty() {
+            let waits_for_deferred = |gated: &GatedPane| {
+                gated
+                    .wait_for
+                    .iter()
+                    .chain(gated.commands.iter().filter_map(|c| c.wait_for.as_ref()))
+                    .filter_map(|wait_for| wait_for.pane.as_ref())
+                    .any(|name| deferred.iter().any(|d| d.name.as_ref() == Some(name)))
             };
+            // fall back to config order if the panes wait for each other
+            let next = deferred
+                .iter()
+                .position(|gated| !waits_for_deferred(gated))
+                .unwrap_or(0);
+            let gated = deferred.remove(next);
+            let target = tmux_target!(&session.name, &gated.window_id, &gated.pane_id);
+
+            if let Some(wait_for) = &gated.wait_for {
+                wait_for.wait(&session.path, &output)?;
+            }
+            if let Some(process) = &gated.process {
+                for wait_for in gated.commands.iter().filter_map(|c| c.wait_for.as_ref()) {
+                    wait_for.wait(&session.path, &output)?;
+                }
+                self.client
+                    .respawn_pane(&target, &gated.path, &gated.env, Some(process))?;
+                continue;
+            }
+            for command in &gated.commands {
+                if let Some(wait_for) = &command.wait_for {
+                    wait_for.wait(&session.path, &output)?;
+                }
+                self.client.send_command(&target, command)?;
+            }
+        }
+        Ok(())
+    }
 
-            Ok(format!(
-                "{}x{},0,0{}{}{}",
-                layout_info.dimensions.width,
-                layout_info.dimensions.height,
-                open_delimiter,
-                pane_strings.join(","),
-                close_delimiter
-            ))
-        } else {
-            Ok(format!(
-                "{}x{},0,0",
-                layout_info.dimensions.width, layout_info.dimensions.height
-            ))
+    fn set_hooks(&self, session: &Session, config: &str) -> Result<()> {
+        for (hook, commands) in [
+            ("client-attached", &session.on_attach),
+            ("client-detached", &session.on_detach),
+        ] {
+            if commands.is_empty() {
+                continue;
+            }
+            self.client.set_hook(
+                &tmux_target!(&session.name),
+                hook,
+                &hook_script(commands, session, config),
+            )?;
         }
+        Ok(())
     }
 
     fn is_laio_session(&self, name: &str) -> Result<bool> {
@@ -362,26 +399,45 @@ impl<R: Runner> Multiplexer for Tmux<R> {
             return Ok(());
         }
 
+        if !skip_cmds {
+            self.client.run_hooks(&session.pre_start, session, config)?;
+        }
+
         let dimensions = self.client.get_dimensions()?;
 
         if !skip_cmds {
-            self.client.run_commands(&session.startup, &session.path)?;
+            self.client.run_startup_commands(session)?;
         }
 
-        let path = session
-            .windows
-            .first()
-            .and_then(|window| window.first_leaf_path())
-            .map(|path| sanitize_path(path, &session.path))
+        let first_window = session.active_windows().into_iter().next();
+
+        let path = first_window
+            .and_then(|window| {
+                window
+                    .first_leaf_path()
+                    .map(|path| sanitize_path(path, &sanitize_path(&window.path, &session.path)))
+            })
             .unwrap_or(session.path.clone());
 
-        self.client.create_session(&session.name, &path)?;
+        self.client
+            .create_session(&session.name, &path, &session.env)?;
         self.client
             .setenv(&tmux_target!(&session.name), LAIO_CONFIG, config);
+        if !session.vars.is_empty() {
+            self.client
+                .setenv(&tmux_target!(&session.name), LAIO_VARS, &save_vars(session));
+        }
+        for (key, value) in &session.env {
+            self.client.setenv(&tmux_target!(&session.name), key, value);
+        }
+        if !skip_cmds {
+            self.set_hooks(session, config)?;
+        }
 
         self.client.flush_commands()?;
 
-        self.process_windows(session, &dimensions, skip_cmds)?;
+        let gates = RefCell::new(Gates::default());
+        let focused_window = self.process_windows(session, &dimensions, skip_cmds, &gates)?;
 
         self.client.bind_key(
             "prefix M-l",
@@ -389,6 +445,17 @@ impl<R: Runner> Multiplexer for Tmux<R> {
         )?;
 
         self.client.flush_commands()?;
+        self.start_gated_panes(session, gates.into_inner())?;
+
+        if let Some(window_id) = focused_window {
+            self.client
+                .select_window(&tmux_target!(&session.name, &window_id))?;
+        }
+
+        if !skip_cmds {
+            self.client
+                .run_hooks(&session.post_start, session, config)?;
+        }
 
         if !skip_attach {
             if self.client.is_inside_session() {
@@ -401,7 +468,14 @@ impl<R: Runner> Multiplexer for Tmux<R> {
         Ok(())
     }
 
-    fn stop(&self, name: &Option<String>, skip_cmds: bool, stop_all: bool) -> Result<()> {
+    fn stop(
+        &self,
+        name: &Option<String>,
+        config_dir: &Path,
+        vars: &HashMap<String, String>,
+        skip_cmds: bool,
+        stop_all: bool,
+    ) -> Result<()> {
         let current_session_name = self.client.current_session_name()?;
         log::trace!("Current session name: {}", current_session_name);
 
@@ -423,7 +497,12 @@ impl<R: Runner> Multiplexer for Tmux<R> {
 
                 if self.is_laio_session(&name)? {
                     log::trace!("Closing session: {:?}", name);
-                    self.stop(&Some(name.to_string()), skip_cmds, false)?;
+                    // one failing session does not keep the others running
+                    if let Err(e) =
+                        self.stop(&Some(name.to_string()), config_dir, vars, skip_cmds, false)
+                    {
+                        log::error!("Failed to stop session {}: {:?}", name, e);
+                    }
                 }
             }
             if !self.client.is_inside_session() {
@@ -447,9 +526,14 @@ impl<R: Runner> Multiplexer for Tmux<R> {
                     Ok(config) => {
                         log::trace!("Config: {:?}", config);
 
-                        let session =
-                            Session::from_config(&resolve_symlink(&to_absolute_path(&config)?)?)?;
-                        self.client.run_commands(&session.shutdown, &session.path)
+                        let vars =
+                            saved_vars(self.client.getenv(&tmux_target!(&name), LAIO_VARS), vars);
+                        let session = Session::from_config(
+                            &resolve_symlink(&to_absolute_path(&config)?)?,
+                            config_dir,
+                            &vars,
+                        )?;
+                        self.client.run_shutdown_commands(&session)
                     }
                     Err(e) => {
                         log::warn!("LAIO_CONFIG environment variable not found: {:?}", e);
@@ -462,7 +546,7 @@ impl<R: Runner> Multiplexer for Tmux<R> {
             }
         })();
 
-        let stop_result = self.client.stop_session(name.as_str()).map_err(Into::into);
+        let stop_result = self.client.stop_session(name.as_str());
 
         result.and(stop_result)
     }
@@ -504,3 +588,40 @@ impl<R: Runner> Multiplexer for Tmux<R> {
         Ok(Session::from_tokens(&name, &path, &tokens))
     }
 }
+
+// Shell script for tmux hooks, which run outside of laio and need the hook env exported.
+fn hook_script(commands: &[Command], session: &Session, config: &str) -> String {
+    let exports: Vec<String> = hook_env(session, config)
+        .into_iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(key, value)| format!("export {}={};", key, shell_quote(&value)))
+        .collect();
+    format!(
+        "{} cd {} && {}",
+        exports.join(" "),
+        shell_quote(&session.path),
+        commands
+            .iter()
+            .map(Command::to_string)
+            .collect::<Vec<_>>()
+            .join(" && ")
+    )
+}
+
+// The process of a leaf pane if it does not run the default shell: its shell,
+// or its commands when they are exec'd.
+fn pane_process(pane: &Pane, commands: &[Command], exec: bool) -> Option<String> {
+    if !exec {
+        return pane.shell.clone();
+    }
+    let script = commands
+        .iter()
+        .map(Command::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    Some(match &pane.shell {
+        Some(shell) => format!("{} -c {}", shell, shell_quote(&script)),
+        None => script,
+    })
+}
@@ -6,6 +6,7 @@ use regex::Regex;
 use crate::common::{
     config::{
         util::{gcd_vec, round},
+        version::VERSION,
         Command, FlexDirection, Pane, Session, Window,
     },
     path::relative_path,
@@ -69,7 +70,16 @@ impl Window {
         Self {
             name: token.name.clone().unwrap_or_else(|| "foo".to_string()),
             flex_direction: pane_flex_direction.clone().unwrap_or_default(),
+            layout: None,
+            grid: None,
+            path: ".".to_string(),
+            env: HashMap::new(),
+            pane_commands: vec![],
+            startup: vec![],
+            shutdown: vec![],
             panes: Pane::from_tokens(&token.children, pane_flex_direction.unwrap_or_default()),
+            focus: false,
+            when: None,
         }
     }
 }
@@ -77,10 +87,18 @@ impl Window {
 impl Session {
     pub(crate) fn from_tokens(name: &str, path: &str, tokens: &[Token]) -> Self {
         Self {
+            version: VERSION,
             name: name.to_string(),
             startup: vec![],
             shutdown: vec![],
+            pre_start: vec![],
+            post_start: vec![],
+            on_attach: vec![],
+            on_detach: vec![],
             env: HashMap::new(),
+            vars: HashMap::new(),
+            lint: HashMap::new(),
+            extends: None,
             path: path.to_string(),
             windows: tokens
                 .iter()
@@ -133,6 +151,9 @@ impl Pane {
                 Pane {
                     flex_direction: pane_flex_direction.clone(),
                     flex: normalized_flex_value,
+                    size: None,
+                    min_size: None,
+                    max_size: None,
                     name: None,
                     style: None,
                     path: match token.path {
@@ -143,6 +164,12 @@ impl Pane {
                     env: HashMap::new(),
                     panes: Pane::from_tokens(&token.children, pane_flex_direction),
                     zoom: false,
+                    focus: false,
+                    wait_for: None,
+                    span: None,
+                    shell: None,
+                    exec: false,
+                    when: None,
                 }
             })
             .inspect(|pane| log::trace!("pane: {:?}", pane))
@@ -273,15 +300,11 @@ fn parse_children<'a>(
         None
     };
     trace!("split_type: {:?}", split_type);
-    while !rest.is_empty()
-        && split_type.is_some()
-        && !rest.starts_with(split_type.as_ref().unwrap().closing_char())
-    {
-        trace!(
-            "split_type: {:?}, {:?}",
-            split_type,
-            Some(split_type.as_ref().unwrap().closing_char())
-        );
+    while let Some(closing_char) = split_type.as_ref().map(SplitType::closing_char) {
+        if rest.is_empty() || rest.starts_with(closing_char) {
+            break;
+        }
+        trace!("split_type: {:?}, {:?}", split_type, Some(closing_char));
         if let Some((child, next_rest)) = parse_single(rest, pane_paths, cmd_dict) {
             children.push(child);
             rest = next_rest;
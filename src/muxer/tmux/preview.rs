@@ -0,0 +1,145 @@
+use std::fmt::Display;
+
+use crate::common::{
+    config::{Cell, Pane, Rect, Session, Window},
+    path::sanitize_path,
+};
+
+// Lines leaving a point of the canvas, joined into one box drawing character.
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+/// Draws the layout tmux would give each window of a session in a `width` x
+/// `height` window, with the name, flex and path of every pane.
+pub(crate) fn preview(session: &Session, width: usize, height: usize) -> String {
+    session
+        .active_windows()
+        .into_iter()
+        .map(|window| preview_window(session, window, width, height))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn preview_window(session: &Session, window: &Window, width: usize, height: usize) -> String {
+    // presets are drawn as the flex panes closest to them
+    let (header, (direction, panes)) = match window.layout {
+        Some(preset) => (
+            format!("{} ({}x{}, {})", window.name, width, height, preset),
+            preset.arrange(&window.active_panes()),
+        ),
+        None => (
+            format!("{} ({}x{})", window.name, width, height),
+            window.flex_panes(),
+        ),
+    };
+    let Some(cell) = Cell::solve(&panes, &direction, width, height) else {
+        return match window.layout {
+            Some(_) => format!("{}: too small to draw its panes\n", header),
+            None => format!(
+                "{}: too small for its panes, tmux keeps the tiled layout\n",
+                header
+            ),
+        };
+    };
+
+    let window_path = sanitize_path(&window.path, &session.path);
+    let mut canvas = Canvas::new(width, height);
+    if panes.is_empty() {
+        canvas.pane(&cell.rect, std::slice::from_ref(&window_path));
+    }
+    draw(&mut canvas, &cell, &panes, &window_path);
+    format!("{}\n{}", header, canvas)
+}
+
+// Draws the innermost panes of a cell, whose nested cells match `panes`.
+fn draw(canvas: &mut Canvas, cell: &Cell, panes: &[Pane], window_path: &String) {
+    for (cell, pane) in cell.children.iter().zip(panes) {
+        let nested = pane.active_panes();
+        if !nested.is_empty() {
+            draw(canvas, cell, &nested, window_path);
+            continue;
+        }
+        let size = match pane.size {
+            Some(size) => format!("size {}", size),
+            None => format!("flex {}", pane.flex),
+        };
+        let lines: Vec<String> = pane
+            .name
+            .iter()
+            .cloned()
+            .chain([size, sanitize_path(&pane.path, window_path)])
+            .collect();
+        canvas.pane(&cell.rect, &lines);
+    }
+}
+
+/// Box drawing of a window, with a border around it. Panes are drawn one
+/// character per cell, the borders between them take the place of the dividers.
+struct Canvas {
+    lines: Vec<Vec<u8>>,
+    text: Vec<Vec<Option<char>>>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            lines: vec![vec![0; width + 2]; height + 2],
+            text: vec![vec![None; width + 2]; height + 2],
+        }
+    }
+
+    fn pane(&mut self, rect: &Rect, lines: &[String]) {
+        let (left, top) = (rect.x, rect.y);
+        let (right, bottom) = (rect.x + rect.width + 1, rect.y + rect.height + 1);
+        for x in left..right {
+            for y in [top, bottom] {
+                self.lines[y][x] |= RIGHT;
+                self.lines[y][x + 1] |= LEFT;
+            }
+        }
+        for y in top..bottom {
+            for x in [left, right] {
+                self.lines[y][x] |= DOWN;
+                self.lines[y + 1][x] |= UP;
+            }
+        }
+        for (row, line) in self.text[top + 1..bottom].iter_mut().zip(lines) {
+            for (cell, c) in row[left + 1..right].iter_mut().zip(line.chars()) {
+                *cell = Some(c);
+            }
+        }
+    }
+}
+
+impl Display for Canvas {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (lines, text) in self.lines.iter().zip(&self.text) {
+            let row: String = lines
+                .iter()
+                .zip(text)
+                .map(|(&lines, &text)| text.unwrap_or_else(|| box_drawing(lines)))
+                .collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+fn box_drawing(lines: u8) -> char {
+    match lines {
+        0 => ' ',
+        l if l == DOWN | RIGHT => '┌',
+        l if l == DOWN | LEFT => '┐',
+        l if l == UP | RIGHT => '└',
+        l if l == UP | LEFT => '┘',
+        l if l == UP | DOWN | RIGHT => '├',
+        l if l == UP | DOWN | LEFT => '┤',
+        l if l == DOWN | LEFT | RIGHT => '┬',
+        l if l == UP | LEFT | RIGHT => '┴',
+        l if l == UP | DOWN | LEFT | RIGHT => '┼',
+        l if l & (LEFT | RIGHT) != 0 => '─',
+        _ => '│',
+    }
+}
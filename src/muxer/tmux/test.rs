@@ -1,28 +1,42 @@
 use crate::{
     common::cmd::{
         test::{MockCmdBoolMock, MockCmdStringMock, MockCmdUnitMock, RunnerMock},
-        Type,
+        Supervision, Type,
     },
     tmux_target,
 };
 use crate::{
-    common::{config::Session, muxer::multiplexer::Multiplexer},
-    muxer::{tmux::Target, Tmux},
+    common::{
+        config::{Cell, FlexDirection, Pane, Session, Size},
+        muxer::{multiplexer::Multiplexer, Client},
+    },
+    muxer::{
+        tmux::{preview::preview, Target},
+        Tmux,
+    },
 };
 use lazy_static::lazy_static;
 use miette::{IntoDiagnostic, Result};
+use proptest::prelude::*;
 use serde_yaml::Value;
 use std::{
-    env::current_dir,
+    collections::HashMap,
+    env::{current_dir, var},
     fs::read_to_string,
-    path::PathBuf,
+    path::{Path, PathBuf},
     rc::Rc,
     str::FromStr,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
 use super::client::TmuxClient;
 
+const CONFIG_DIR: &str = "src/common/config/test";
+
 #[test]
 fn client_create_session() -> Result<()> {
     let mut cmd_unit = MockCmdUnitMock::new();
@@ -56,8 +70,12 @@ fn client_create_session() -> Result<()> {
     let tmux_client = TmuxClient::new(Rc::new(runner));
     let session_name = "test";
 
-    tmux_client.create_session(&String::from("test"), &String::from("/tmp"))?;
-    tmux_client.new_window(session_name, "test", "/tmp")?;
+    tmux_client.create_session(
+        &String::from("test"),
+        &String::from("/tmp"),
+        &HashMap::new(),
+    )?;
+    tmux_client.new_window(session_name, "test", "/tmp", &HashMap::new())?;
     tmux_client.select_layout(&tmux_target!(session_name, "@1"), "main-horizontal")?;
     Ok(())
 }
@@ -71,7 +89,7 @@ lazy_static! {
 fn mux_start_session() {
     let path = PathBuf::from_str("./src/common/config/test/valid.yaml").unwrap();
 
-    let session = Session::from_config(&path).unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new()).unwrap();
 
     let mut cmd_unit = MockCmdUnitMock::new();
     let mut cmd_string = MockCmdStringMock::new();
@@ -102,20 +120,29 @@ fn mux_start_session() {
         .expect_run()
         .times(2)
         .withf(
-            |cmd| matches!(cmd, Type::Verbose(content) if vec!["date", "echo Hi"].contains(&content.as_str())),
+            |cmd| matches!(cmd, Type::Verbose(content) if ["date", "echo Hi"].contains(&content.as_str())),
         )
-        .returning(|_| Ok("".to_string()));
+        .returning(|_| {
+            assert_eq!(var("FOO").as_deref(), Ok("BAR"));
+            Ok("".to_string())
+        });
+
+    cmd_unit
+        .expect_run()
+        .times(1)
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux new-session -d -s \"valid\" -c \"/tmp\" -e 'FOO=BAR'" ))
+        .returning(|_| Ok(()));
 
     cmd_unit
         .expect_run()
         .times(1)
-        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux new-session -d -s \"valid\" -c \"/tmp\"" ))
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux setenv -t \"valid\" FOO 'BAR'" ))
         .returning(|_| Ok(()));
 
     cmd_unit
         .expect_run()
         .times(1)
-        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux setenv -t \"valid\" LAIO_CONFIG \"./src/common/config/test/valid.yaml\"" ))
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux setenv -t \"valid\" LAIO_CONFIG './src/common/config/test/valid.yaml'" ))
         .returning(|_| Ok(()));
 
     cmd_string
@@ -181,7 +208,7 @@ fn mux_start_session() {
     cmd_string
         .expect_run()
         .times(1)
-        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux split-window -t \"valid\":@1 -c \"/tmp/src\" -P -F \"#{pane_id}\"" ))
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux split-window -t \"valid\":@1 -c \"/tmp/src\" -e 'FOO=PANE' -P -F \"#{pane_id}\"" ))
         .returning(|_| {
                 let value = PANE_NUM.fetch_add(1, Ordering::SeqCst) + 1;
                 Ok(format!("%{}", value))
@@ -191,7 +218,7 @@ fn mux_start_session() {
     cmd_unit
         .expect_run()
         .times(1)
-        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux select-layout -t \"valid\":@1 \"83ed,160x90,0,0[160x45,0,0{53x45,0,0,2,106x45,54,0,3},160x44,0,46,4]\""))
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux select-layout -t \"valid\":@1 \"52e9,160x90,0,0[160x44,0,0{53x44,0,0,2,106x44,54,0,3},160x45,0,45,4]\""))
         .returning(|_| Ok(()));
 
     cmd_string
@@ -243,7 +270,19 @@ fn mux_start_session() {
     cmd_unit
         .expect_run()
         .times(1)
-        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux select-layout -t \"valid\":@2 \"149e,160x90,0,0[160x22,0,0,5,160x45,0,23,6,160x21,0,69,7]\""))
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux select-layout -t \"valid\":@2 \"f19d,160x90,0,0[160x22,0,0,5,160x44,0,23,6,160x22,0,68,7]\""))
+        .returning(|_| Ok(()));
+
+    cmd_unit
+        .expect_run()
+        .times(1)
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux select-pane -t \"valid\":@2.%6"))
+        .returning(|_| Ok(()));
+
+    cmd_unit
+        .expect_run()
+        .times(1)
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux select-window -t \"valid\":@2"))
         .returning(|_| Ok(()));
 
     cmd_unit
@@ -261,7 +300,7 @@ fn mux_start_session() {
     cmd_unit
         .expect_run()
         .times(1)
-        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux send-keys -t \"valid\":@1.%4 'tmux resize-pane -Z -t \"valid\":@1.%4' C-m"))
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux resize-pane -Z -t \"valid\":@1.%4"))
         .returning(|_| Ok(()));
 
     cmd_unit
@@ -272,7 +311,7 @@ fn mux_start_session() {
 
     cmd_unit
         .expect_run()
-        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux send-keys -t \"valid\":@1.%1 'tmux select-pane -t \"valid\":@1.%1 -T foo ' C-m"))
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux select-pane -t \"valid\":@1.%1 -T \"foo\""))
         .times(1)
         .returning(|_| Ok(()));
 
@@ -350,11 +389,20 @@ fn mux_stop_session() -> Result<()> {
         .times(2)
         .returning(|_| Ok("LAIO_CONFIG=./src/common/config/test/valid.yaml".to_string()));
 
+    cmd_string
+        .expect_run()
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "tmux show-environment -t \"valid\" LAIO_VARS"))
+        .times(1)
+        .returning(|_| Ok("-LAIO_VARS".to_string()));
+
     cmd_string
         .expect_run()
         .withf(|cmd| matches!(cmd, Type::Verbose(content) if content == "date"))
         .times(1)
-        .returning(|_| Ok("something".to_string()));
+        .returning(|_| {
+            assert_eq!(var("FOO").as_deref(), Ok("BAR"));
+            Ok("something".to_string())
+        });
 
     cmd_string
         .expect_run()
@@ -376,7 +424,13 @@ fn mux_stop_session() -> Result<()> {
 
     let tmux = Tmux::new_with_runner(runner);
 
-    let result = tmux.stop(&Some("valid".to_string()), false, false);
+    let result = tmux.stop(
+        &Some("valid".to_string()),
+        Path::new(CONFIG_DIR),
+        &HashMap::new(),
+        false,
+        false,
+    );
 
     assert!(result.is_ok());
     Ok(())
@@ -494,3 +548,777 @@ fn mux_list_sessions() -> Result<()> {
 
     Ok(())
 }
+
+// Commands run through a recording runner, in order.
+type Recorded = Arc<Mutex<Vec<String>>>;
+
+// A runner that records every command and answers like tmux with a 160x90
+// window: every pane gets its own id and the last one created in a window is
+// its current pane. `exists` answers checks like has-session, `output`
+// overrides the output of a command.
+fn recording_runner(
+    exists: bool,
+    output: impl Fn(&Type) -> Option<String> + Send + 'static,
+) -> (RunnerMock, Recorded) {
+    let cmds: Recorded = Arc::new(Mutex::new(vec![]));
+    let (mut windows, mut panes) = (1, 1);
+    let mut current = HashMap::from([("@1".to_string(), "%1".to_string())]);
+
+    let mut cmd_unit = MockCmdUnitMock::new();
+    let mut cmd_string = MockCmdStringMock::new();
+    let mut cmd_bool = MockCmdBoolMock::new();
+
+    cmd_bool.expect_run().returning({
+        let cmds = cmds.clone();
+        move |cmd| {
+            cmds.lock().unwrap().push(cmd.to_string());
+            Ok(exists)
+        }
+    });
+    cmd_string.expect_run().returning({
+        let cmds = cmds.clone();
+        move |cmd| {
+            cmds.lock().unwrap().push(cmd.to_string());
+            if let Some(output) = output(cmd) {
+                return Ok(output);
+            }
+            let cmd = cmd.to_string();
+            let mut new_pane = |window: String| {
+                panes += 1;
+                let pane = format!("%{}", panes);
+                current.insert(window, pane.clone());
+                pane
+            };
+            Ok(match cmd.as_str() {
+                "printenv TMUX" => "something".to_string(),
+                "tmux show-options -g base-index" => "base-index 1".to_string(),
+                cmd if cmd.contains("window_width") => "width: 160\nheight: 90".to_string(),
+                cmd if cmd.contains("#I") => "@1".to_string(),
+                cmd if cmd.contains("new-window") => {
+                    windows += 1;
+                    let window = format!("@{}", windows);
+                    new_pane(window.clone());
+                    window
+                }
+                cmd if cmd.contains("split-window") => new_pane(target_window(cmd)),
+                cmd if cmd.contains("#P") => current
+                    .get(&target_window(cmd))
+                    .cloned()
+                    .unwrap_or_default(),
+                _ => String::new(),
+            })
+        }
+    });
+    cmd_unit.expect_run().returning({
+        let cmds = cmds.clone();
+        move |cmd| {
+            cmds.lock().unwrap().push(cmd.to_string());
+            Ok(())
+        }
+    });
+
+    let runner = RunnerMock {
+        cmd_unit,
+        cmd_string,
+        cmd_bool,
+    };
+    (runner, cmds)
+}
+
+// The window a command targets, e.g. `@1` of `-t "grid":@1.%2`.
+fn target_window(cmd: &str) -> String {
+    let target = cmd.split_once(":@").map_or("", |(_, target)| target);
+    let id: String = target.chars().take_while(char::is_ascii_digit).collect();
+    format!("@{}", id)
+}
+
+// Position of a recorded command, failing if it was not run.
+fn position(cmds: &[String], expected: &str) -> usize {
+    cmds.iter()
+        .position(|cmd| cmd == expected)
+        .unwrap_or_else(|| panic!("{} not found in {:#?}", expected, cmds))
+}
+
+#[test]
+fn mux_start_session_window_defaults() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/window_defaults.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    let (runner, cmds) = recording_runner(false, |_| None);
+
+    Tmux::new_with_runner(runner).start(&session, "window_defaults.yaml", true, false)?;
+
+    let cmds = cmds.lock().unwrap();
+    // the window env is scoped to the panes of the window, not the session
+    assert!(
+        position(&cmds, "tmux new-session -d -s \"defaults\" -c \"/tmp/app\"")
+            < position(
+                &cmds,
+                "tmux respawn-pane -k -t \"defaults\":@1.%1 -c \"/tmp/app\" -e 'VIRTUAL_ENV=.venv'"
+            )
+    );
+    assert!(
+        position(
+            &cmds,
+            "tmux send-keys -t \"defaults\":@1.%1 'source .venv/bin/activate' C-m"
+        ) < position(&cmds, "tmux send-keys -t \"defaults\":@1.%1 'pytest' C-m")
+    );
+    position(
+        &cmds,
+        "tmux send-keys -t \"defaults\":@1.%2 'source .venv/bin/activate' C-m",
+    );
+    Ok(())
+}
+
+#[test]
+fn mux_stop_session_window_hooks() -> Result<()> {
+    let (runner, cmds) = recording_runner(true, |cmd| match cmd {
+        Type::Basic(cmd) if cmd.contains("LAIO_CONFIG") => {
+            Some("LAIO_CONFIG=./src/common/config/test/hooks.yaml".to_string())
+        }
+        Type::Basic(_) => Some("hooks".to_string()),
+        cmd => {
+            assert_eq!(var("FOO").as_deref(), Ok("BAR"));
+            if cmd.to_string().starts_with("pkill") {
+                assert_eq!(var("KUBE_CONTEXT").as_deref(), Ok("dev"));
+            }
+            None
+        }
+    });
+
+    Tmux::new_with_runner(runner).stop(
+        &Some("hooks".to_string()),
+        Path::new(CONFIG_DIR),
+        &HashMap::new(),
+        false,
+        false,
+    )?;
+
+    let cmds = cmds.lock().unwrap();
+    assert!(
+        position(&cmds, "pkill -f port-forward") < position(&cmds, "echo bye")
+            && position(&cmds, "echo bye") < position(&cmds, "tmux kill-session -t \"hooks\"")
+    );
+    assert_eq!(
+        cmds.iter()
+            .filter(|cmd| cmd.starts_with("tmux kill-session"))
+            .count(),
+        1
+    );
+    Ok(())
+}
+
+#[test]
+fn mux_start_session_lifecycle_hooks() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/lifecycle.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    let (runner, cmds) = recording_runner(false, |cmd| {
+        if let Type::Verbose(_) = cmd {
+            assert_eq!(var("LAIO_SESSION").as_deref(), Ok("lifecycle"));
+            assert_eq!(var("LAIO_PATH").as_deref(), Ok("/tmp"));
+        }
+        None
+    });
+
+    Tmux::new_with_runner(runner).start(&session, "lifecycle.yaml", false, false)?;
+
+    let cmds = cmds.lock().unwrap();
+    assert!(
+        position(&cmds, "echo pre")
+            < position(&cmds, "tmux new-session -d -s \"lifecycle\" -c \"/tmp\"")
+    );
+    assert!(
+        position(&cmds, "tmux rename-window -t \"lifecycle\":@1 \"code\"")
+            < position(&cmds, "echo post")
+    );
+    assert!(position(&cmds, "echo post") < position(&cmds, "tmux switch-client -t \"lifecycle\""));
+    position(
+        &cmds,
+        concat!(
+            r#"tmux set-hook -t "lifecycle" client-attached 'run-shell -b "export LAIO_CONFIG='\''lifecycle.yaml'\''; "#,
+            r#"export LAIO_PATH='\''/tmp'\''; export LAIO_SESSION='\''lifecycle'\''; "#,
+            r#"cd '\''/tmp'\'' && echo \"attached \$LAIO_SESSION\""'"#
+        ),
+    );
+    position(
+        &cmds,
+        concat!(
+            r#"tmux set-hook -t "lifecycle" client-detached 'run-shell -b "export LAIO_CONFIG='\''lifecycle.yaml'\''; "#,
+            r#"export LAIO_PATH='\''/tmp'\''; export LAIO_SESSION='\''lifecycle'\''; "#,
+            r#"cd '\''/tmp'\'' && echo detached"'"#
+        ),
+    );
+    Ok(())
+}
+
+#[test]
+fn mux_start_session_wait_for() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/wait_for/valid.yaml").unwrap();
+    let session = Session {
+        startup: vec![],
+        ..Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?
+    };
+    let (runner, cmds) = recording_runner(false, |cmd| {
+        cmd.to_string()
+            .contains("capture-pane")
+            .then(|| "Compiling\nlistening on 8080".to_string())
+    });
+
+    Tmux::new_with_runner(runner).start(&session, "valid.yaml", true, false)?;
+
+    let cmds = cmds.lock().unwrap();
+    assert!(
+        position(
+            &cmds,
+            "tmux send-keys -t \"wait_for\":@1.%2 'cargo run' C-m"
+        ) < position(&cmds, "tmux capture-pane -p -J -S - -t \"wait_for\":@1.%2")
+    );
+    assert!(
+        position(&cmds, "tmux capture-pane -p -J -S - -t \"wait_for\":@1.%2")
+            < position(
+                &cmds,
+                "tmux send-keys -t \"wait_for\":@1.%1 'npm start' C-m"
+            )
+    );
+    Ok(())
+}
+
+#[test]
+fn client_register_command_options() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/commands.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    let tmux_client = TmuxClient::new(Rc::new(RunnerMock {
+        cmd_unit: MockCmdUnitMock::new(),
+        cmd_string: MockCmdStringMock::new(),
+        cmd_bool: MockCmdBoolMock::new(),
+    }));
+    let target = tmux_target!("commands", "@1", "%1");
+
+    for pane in &session.windows[0].panes {
+        tmux_client.register_commands(&target, &pane.commands);
+    }
+
+    let cmds: Vec<String> = tmux_client
+        .cmds
+        .borrow()
+        .iter()
+        .map(|cmd| cmd.to_string())
+        .collect();
+    assert_eq!(
+        cmds,
+        vec![
+            "tmux send-keys -t \"commands\":@1.%1 'git commit -m \"it'\\''s done\"'",
+            "tmux send-keys -l -t \"commands\":@1.%1 'echo Enter'",
+            "tmux send-keys -t \"commands\":@1.%1 C-m",
+            "sleep 1.500",
+            "tmux send-keys -t \"commands\":@1.%1 'ls' C-m",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn client_run_commands_policy() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/command_policy.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    let cmds = Arc::new(Mutex::new(Vec::<Type>::new()));
+
+    let mut cmd_string = MockCmdStringMock::new();
+    cmd_string.expect_run().returning({
+        let cmds = cmds.clone();
+        move |cmd| {
+            let mut cmds = cmds.lock().unwrap();
+            cmds.push(cmd.clone());
+            let flaky = cmds.iter().filter(|c| c.to_string() == "flaky").count();
+            match cmd.to_string().as_str() {
+                "flaky" if flaky < 3 => Err(miette::miette!("Command failed with exit code 1")),
+                "lint" | "deploy" => Err(miette::miette!("Command failed with exit code 2")),
+                _ => Ok(String::new()),
+            }
+        }
+    });
+    let tmux_client = TmuxClient::new(Rc::new(RunnerMock {
+        cmd_unit: MockCmdUnitMock::new(),
+        cmd_string,
+        cmd_bool: MockCmdBoolMock::new(),
+    }));
+
+    let err = tmux_client
+        .run_startup_commands(&session)
+        .expect_err("Expected deploy to fail");
+    assert_eq!(err.to_string(), "Command failed with exit code 2");
+
+    let cmds = cmds.lock().unwrap();
+    assert_eq!(
+        cmds.iter().map(|cmd| cmd.to_string()).collect::<Vec<_>>(),
+        vec![
+            "flaky",
+            "flaky",
+            "flaky",
+            "lint",
+            "cd '/' && build",
+            "deploy"
+        ]
+    );
+    assert_eq!(
+        cmds[4],
+        Type::Supervised(
+            "cd '/' && build".to_string(),
+            Supervision {
+                timeout: Some(Duration::from_secs(5)),
+                prefix: None,
+            }
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn client_run_commands_parallel() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/parallel/valid.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    let cmds = Arc::new(Mutex::new(Vec::<Type>::new()));
+
+    let mut cmd_string = MockCmdStringMock::new();
+    cmd_string.expect_run().returning({
+        let cmds = cmds.clone();
+        move |cmd| {
+            cmds.lock().unwrap().push(cmd.clone());
+            match cmd.to_string().as_str() {
+                "lint" => Err(miette::miette!("Command failed with exit code 1: lint")),
+                "test" => Err(miette::miette!("Command failed with exit code 2: test")),
+                _ => Ok(String::new()),
+            }
+        }
+    });
+    let tmux_client = TmuxClient::new(Rc::new(RunnerMock {
+        cmd_unit: MockCmdUnitMock::new(),
+        cmd_string,
+        cmd_bool: MockCmdBoolMock::new(),
+    }));
+
+    let err = tmux_client
+        .run_startup_commands(&session)
+        .expect_err("Expected the second group to fail");
+    assert_eq!(err.to_string(), "Command failed with exit code 2: test");
+
+    let cmds = cmds.lock().unwrap();
+    let position = |expected: &str| cmds.iter().position(|cmd| cmd.to_string() == expected);
+    let make = position("make").expect("make not run");
+    for pulled in ["docker pull postgres", "docker pull redis", "colima start"] {
+        assert!(position(pulled).expect("group command not run") < make);
+    }
+    assert!(position("lint").unwrap() > make);
+    assert!(position("test").unwrap() > make);
+    assert_eq!(position("deploy"), None);
+    assert!(cmds.contains(&Type::Supervised(
+        "docker pull redis".to_string(),
+        Supervision {
+            timeout: None,
+            prefix: Some("2:docker".to_string()),
+        }
+    )));
+    Ok(())
+}
+
+#[test]
+fn mux_start_session_exec() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/exec.yaml").unwrap();
+    let mut session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    session.windows[0]
+        .env
+        .insert("EDITOR".to_string(), "nvim".to_string());
+    let (runner, cmds) = recording_runner(false, |_| None);
+
+    Tmux::new_with_runner(runner).start(&session, "exec.yaml", true, false)?;
+
+    let cmds = cmds.lock().unwrap();
+    for cmd in [
+        r#"tmux respawn-pane -k -t "exec":@1.%1 -c "/tmp" -e 'EDITOR=nvim' '/usr/bin/fish'"#,
+        r#"tmux send-keys -t "exec":@1.%1 'nvim' C-m"#,
+        r##"tmux split-window -t "exec":@1 -c "/tmp" -e 'EDITOR=nvim' -P -F "#{pane_id}" 'tail -f app.log'"##,
+        r#"tmux select-pane -t "exec":@1.%2 -T "logs""#,
+        r##"tmux split-window -t "exec":@1 -c "/tmp" -e 'EDITOR=nvim' -P -F "#{pane_id}" '/bin/bash -c '\''htop'\'''"##,
+        r#"tmux respawn-pane -k -t "exec":@2.%4 -c "/tmp" 'btop'"#,
+    ] {
+        position(&cmds, cmd);
+    }
+    // the window env is applied along with the shell, not by a respawn of its own
+    assert_eq!(
+        cmds.iter()
+            .filter(|c| c.starts_with(r#"tmux respawn-pane -k -t "exec":@1.%1 "#))
+            .count(),
+        1
+    );
+    for exec in ["tail", "htop", "btop"] {
+        assert!(
+            !cmds
+                .iter()
+                .any(|c| c.starts_with("tmux send-keys") && c.contains(exec)),
+            "{} was typed into its pane",
+            exec
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn mux_start_session_layout_preset() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/layouts.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    let (runner, cmds) = recording_runner(false, |_| None);
+
+    Tmux::new_with_runner(runner).start(&session, "layouts.yaml", true, false)?;
+
+    let cmds = cmds.lock().unwrap();
+    let layouts: Vec<&String> = cmds
+        .iter()
+        .filter(|cmd| cmd.starts_with("tmux select-layout") && !cmd.ends_with("\"tiled\""))
+        .collect();
+    assert_eq!(
+        layouts,
+        vec![r#"tmux select-layout -t "layouts":@1 "main-vertical""#]
+    );
+    assert_eq!(
+        cmds.iter()
+            .filter(|cmd| cmd.contains("split-window"))
+            .count(),
+        6
+    );
+    position(&cmds, r#"tmux send-keys -t "layouts":@1.%1 'nvim' C-m"#);
+    Ok(())
+}
+
+#[test]
+fn mux_start_session_grid() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/grid.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    let (runner, cmds) = recording_runner(false, |_| None);
+
+    Tmux::new_with_runner(runner).start(&session, "grid.yaml", true, false)?;
+
+    let cmds = cmds.lock().unwrap();
+    let monitoring: Vec<&String> = cmds
+        .iter()
+        .filter(|cmd| cmd.contains(r#""grid":@1"#))
+        .collect();
+    assert_eq!(
+        monitoring
+            .iter()
+            .filter(|cmd| cmd.starts_with("tmux split-window"))
+            .count(),
+        4
+    );
+    let layout = monitoring
+        .iter()
+        .find(|cmd| cmd.starts_with("tmux select-layout") && !cmd.ends_with(r#""tiled""#))
+        .expect("Expected the grid layout");
+    // two rows of panes side by side
+    assert!(layout.contains(",160x90,0,0["));
+    assert_eq!(layout.matches('{').count(), 2);
+
+    // spanning panes nest deeper, every pane keeps its own cell
+    let dashboard: Vec<&String> = cmds
+        .iter()
+        .filter(|cmd| cmd.contains(r#""grid":@2"#))
+        .collect();
+    assert_eq!(
+        dashboard
+            .iter()
+            .filter(|cmd| cmd.starts_with("tmux split-window"))
+            .count(),
+        3
+    );
+    let layout = dashboard
+        .iter()
+        .find(|cmd| cmd.starts_with("tmux select-layout") && !cmd.ends_with(r#""tiled""#))
+        .expect("Expected the grid layout");
+    assert_eq!(
+        layout.as_str(),
+        r#"tmux select-layout -t "grid":@2 "87d3,160x90,0,0{53x90,0,0,6,106x90,54,0[106x44,54,0{52x44,54,0,7,53x44,107,0,8},106x45,54,45,9]}""#
+    );
+    // each pane of the window is in the layout once, as tmux requires
+    let (_, layout) = layout.trim_end_matches('"').split_once(',').unwrap();
+    let mut ids = vec![];
+    check_layout(&parse_layout(layout), &mut ids);
+    assert_eq!(ids, vec!["6", "7", "8", "9"]);
+    Ok(())
+}
+
+// A parsed tmux layout cell: size, offset and either a pane id or nested cells
+// split side by side (`{}`) or stacked (`[]`).
+#[derive(Debug)]
+struct LayoutCell {
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    id: Option<String>,
+    split: Option<(FlexDirection, Vec<LayoutCell>)>,
+}
+
+fn parse_layout(layout: &str) -> LayoutCell {
+    fn number(layout: &mut &str, end: &[char]) -> usize {
+        let len = layout.find(end).unwrap_or(layout.len());
+        let (number, rest) = layout.split_at(len);
+        *layout = rest.get(1..).unwrap_or("");
+        number.parse().expect("Expected a number")
+    }
+    fn cell(layout: &mut &str) -> LayoutCell {
+        let width = number(layout, &['x']);
+        let height = number(layout, &[',']);
+        let x = number(layout, &[',']);
+        let len = layout
+            .find([',', '{', '[', '}', ']'])
+            .unwrap_or(layout.len());
+        let y = layout[..len].parse().expect("Expected a number");
+        *layout = &layout[len..];
+        let mut parsed = LayoutCell {
+            width,
+            height,
+            x,
+            y,
+            id: None,
+            split: None,
+        };
+        let (direction, close) = match layout.chars().next() {
+            Some('{') => (FlexDirection::Row, '}'),
+            Some('[') => (FlexDirection::Column, ']'),
+            Some(',') => {
+                // either the pane id or the next cell
+                let rest = &layout[1..];
+                let len = rest.find([',', '}', ']']).unwrap_or(rest.len());
+                if rest[..len].bytes().all(|b| b.is_ascii_digit()) {
+                    parsed.id = Some(rest[..len].to_string());
+                    *layout = &rest[len..];
+                }
+                return parsed;
+            }
+            _ => return parsed,
+        };
+        *layout = &layout[1..];
+        let mut cells = vec![cell(layout)];
+        while layout.starts_with(',') {
+            *layout = &layout[1..];
+            cells.push(cell(layout));
+        }
+        assert!(
+            layout.starts_with(close),
+            "Expected '{}' in {}",
+            close,
+            layout
+        );
+        *layout = &layout[1..];
+        parsed.split = Some((direction, cells));
+        parsed
+    }
+    let mut rest = layout;
+    let parsed = cell(&mut rest);
+    assert!(rest.is_empty(), "Unexpected '{}' after the layout", rest);
+    parsed
+}
+
+// Checks that the nested cells fill their parent exactly, one divider apart,
+// collecting the pane ids in order.
+fn check_layout(cell: &LayoutCell, ids: &mut Vec<String>) {
+    assert!(cell.width > 0 && cell.height > 0, "Empty cell {:?}", cell);
+    let Some((direction, cells)) = &cell.split else {
+        ids.extend(cell.id.clone());
+        return;
+    };
+    assert!(cells.len() > 1, "Single nested cell in {:?}", cell);
+    let mut offset = match direction {
+        FlexDirection::Row => cell.x,
+        FlexDirection::Column => cell.y,
+    };
+    for nested in cells {
+        let (start, size) = match direction {
+            FlexDirection::Row => {
+                assert_eq!((nested.height, nested.y), (cell.height, cell.y));
+                (nested.x, nested.width)
+            }
+            FlexDirection::Column => {
+                assert_eq!((nested.width, nested.x), (cell.width, cell.x));
+                (nested.y, nested.height)
+            }
+        };
+        assert_eq!(start, offset, "Misplaced cell {:?}", nested);
+        offset += size + 1;
+        check_layout(nested, ids);
+    }
+    let end = match direction {
+        FlexDirection::Row => cell.x + cell.width,
+        FlexDirection::Column => cell.y + cell.height,
+    };
+    assert_eq!(offset - 1, end, "Cells do not fill {:?}", cell);
+}
+
+fn leaves(panes: &[Pane]) -> usize {
+    panes
+        .iter()
+        .map(|pane| match pane.active_panes().as_slice() {
+            [] => 1,
+            panes => leaves(panes),
+        })
+        .sum()
+}
+
+fn direction_strategy() -> impl Strategy<Value = FlexDirection> {
+    prop_oneof![Just(FlexDirection::Row), Just(FlexDirection::Column)]
+}
+
+fn size_strategy() -> impl Strategy<Value = Option<Size>> {
+    prop_oneof![
+        2 => Just(None),
+        1 => (1..60usize).prop_map(|cells| Some(Size::Cells(cells))),
+        1 => (1..100usize).prop_map(|percent| Some(Size::Percent(percent))),
+    ]
+}
+
+fn pane_strategy() -> impl Strategy<Value = Pane> {
+    let leaf = (1..5usize, size_strategy(), size_strategy(), size_strategy()).prop_map(
+        |(flex, size, min_size, max_size)| Pane {
+            size,
+            min_size,
+            max_size,
+            ..Pane::container(FlexDirection::Row, flex, vec![])
+        },
+    );
+    leaf.prop_recursive(4, 32, 4, |pane| {
+        (
+            direction_strategy(),
+            1..5usize,
+            size_strategy(),
+            prop::collection::vec(pane, 1..5),
+        )
+            .prop_map(|(direction, flex, size, panes)| Pane {
+                size,
+                ..Pane::container(direction, flex, panes)
+            })
+    })
+}
+
+proptest! {
+    #[test]
+    fn mux_layout_fills_window(
+        direction in direction_strategy(),
+        panes in prop::collection::vec(pane_strategy(), 0..5),
+        width in 1..300usize,
+        height in 1..120usize,
+    ) {
+        let cell = Cell::solve(&panes, &direction, width, height);
+        prop_assume!(cell.is_some());
+        let cell = cell.unwrap();
+
+        let expected: Vec<String> = (0..leaves(&panes)).map(|id| format!("%{}", id)).collect();
+        let layout = parse_layout(&cell.layout(&mut expected.clone().into_iter()));
+        prop_assert_eq!((layout.width, layout.height, layout.x, layout.y), (width, height, 0, 0));
+
+        let mut ids = vec![];
+        check_layout(&layout, &mut ids);
+        let expected: Vec<String> = expected.iter().map(|id| id.replace('%', "")).collect();
+        prop_assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn mux_layout_fits_minimum(
+        direction in direction_strategy(),
+        panes in prop::collection::vec(pane_strategy(), 1..5),
+    ) {
+        // the smallest window a layout fits in has a cell per pane and divider
+        let width = (1..).find(|&w| Cell::solve(&panes, &direction, w, 1000).is_some()).unwrap();
+        let height = (1..).find(|&h| Cell::solve(&panes, &direction, 1000, h).is_some()).unwrap();
+        prop_assert!(width < 2 * leaves(&panes));
+        prop_assert!(height < 2 * leaves(&panes));
+        let layout = parse_layout(&Cell::solve(&panes, &direction, width, height).unwrap().layout(&mut std::iter::empty()));
+        check_layout(&layout, &mut vec![]);
+    }
+}
+
+#[test]
+fn mux_preview() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/preview/preview.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    assert_eq!(
+        preview(&session, 30, 8),
+        "\
+code (30x8)
+┌───────────────────┬──────────┐
+│editor             │shell     │
+│flex 2             │flex 1    │
+│/tmp               │/tmp/src  │
+│                   │          │
+│                   ├──────────┤
+│                   │logs      │
+│                   │size 3    │
+│                   │/tmp      │
+└───────────────────┴──────────┘
+
+docs (30x8, main-vertical)
+┌───────────────────┬──────────┐
+│a                  │b         │
+│flex 2             │flex 1    │
+│/tmp               │/tmp      │
+│                   ├──────────┤
+│                   │c         │
+│                   │flex 1    │
+│                   │/tmp      │
+│                   │          │
+└───────────────────┴──────────┘
+"
+    );
+    // a cell per pane and divider
+    assert!(preview(&session, 30, 2).starts_with("code (30x2): too small for its panes"));
+    assert!(preview(&session, 30, 3).starts_with("code (30x3)\n┌"));
+    Ok(())
+}
+
+#[test]
+fn mux_stop_all_sessions_saved_vars() -> Result<()> {
+    let (runner, cmds) = recording_runner(true, |cmd| {
+        Some(match cmd.to_string().as_str() {
+            "tmux ls -F \"#{session_name}\"" => "broken\nvars".to_string(),
+            "tmux show-environment -t \"broken\" LAIO_CONFIG" => {
+                "LAIO_CONFIG=./src/common/config/test/missing.yaml".to_string()
+            }
+            "tmux show-environment -t \"vars\" LAIO_CONFIG" => {
+                "LAIO_CONFIG=./src/common/config/test/stop_vars.yaml".to_string()
+            }
+            // the var was only given on the command line
+            "tmux show-environment -t \"vars\" LAIO_VARS" => {
+                r#"LAIO_VARS={"ticket":"42"}"#.to_string()
+            }
+            _ => "".to_string(),
+        })
+    });
+
+    Tmux::new_with_runner(runner).stop(
+        &None,
+        Path::new(CONFIG_DIR),
+        &HashMap::new(),
+        false,
+        true,
+    )?;
+
+    let cmds = cmds.lock().unwrap();
+    position(&cmds, "echo 42");
+    // a failing session does not keep the others running
+    position(&cmds, "tmux kill-session -t \"broken\"");
+    position(&cmds, "tmux kill-session -t \"vars\"");
+    Ok(())
+}
+
+#[test]
+fn mux_start_existing_session_skips_hooks() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/lifecycle.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+    let (runner, cmds) = recording_runner(true, |_| None);
+
+    Tmux::new_with_runner(runner).start(&session, "lifecycle.yaml", false, false)?;
+
+    // switching to a running session does not run pre_start
+    let cmds = cmds.lock().unwrap();
+    position(&cmds, "tmux switch-client -t \"lifecycle\"");
+    assert!(!cmds.contains(&"echo pre".to_string()), "{:#?}", cmds);
+    Ok(())
+}
@@ -1,16 +1,26 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     env::temp_dir,
     fs::{remove_file, File},
     rc::Rc,
+    thread::sleep,
+    time::Duration,
 };
 
-use crate::common::{muxer::client::Client, path::sanitize_filename};
+use crate::common::{
+    muxer::client::{shell_quote, Client},
+    path::sanitize_filename,
+};
 use crate::{
     cmd_basic, cmd_forget,
     common::cmd::{Runner, Type},
 };
 use kdl::{KdlDocument, KdlNode};
-use miette::{IntoDiagnostic, Result};
+use miette::{bail, IntoDiagnostic, Result};
+
+// zellij gets 10 seconds to create a session in the background
+const SESSION_POLLS: usize = 100;
+const SESSION_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Debug)]
 pub(crate) struct ZellijClient<R: Runner> {
@@ -33,40 +43,48 @@ impl<R: Runner> ZellijClient<R> {
         name: &str,
         config: &str,
         layout: &str,
-        skip_attach: bool,
+        env: &HashMap<String, String>,
     ) -> Result<()> {
-        let cmd = if skip_attach {
-            // workaround as zellij doesn't yet support backgrounding when creating with a layout.
-            &cmd_forget!(
-                "LAIO_CONFIG={} nohup zellij --session {} --new-session-with-layout {} > /dev/null 2>&1 </dev/null & disown",
-              config,
-              name,
-              layout
-            )
-        } else {
-            &cmd_forget!(
-                "LAIO_CONFIG={} zellij --session {} --new-session-with-layout {}",
-                config,
-                name,
-                layout
-            )
-        };
-        self.cmd_runner.run(cmd)
+        let env = env
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(key, value)| format!("{}={} ", key, shell_quote(value)))
+            .collect::<String>();
+        // workaround as zellij doesn't yet support backgrounding when creating with a layout.
+        self.cmd_runner.run(&cmd_forget!(
+            "{}LAIO_CONFIG={} nohup zellij --session {} --new-session-with-layout {} > /dev/null 2>&1 </dev/null & disown",
+            env,
+            config,
+            name,
+            layout
+        ))
     }
 
     pub(crate) fn stop_session(&self, name: &str) -> Result<()> {
-        self.session_exists(name)
-            .then(|| {
-                self.cmd_runner
-                    .run(&cmd_basic!("zellij delete-session \"{}\" --force", name))
-            })
-            .unwrap_or(Ok(()))
+        if self.session_exists(name) {
+            self.cmd_runner
+                .run(&cmd_basic!("zellij delete-session \"{}\" --force", name))
+        } else {
+            Ok(())
+        }
     }
 
     pub(crate) fn attach(&self, name: &str) -> Result<()> {
         self.cmd_runner.run(&cmd_forget!("zellij attach {} ", name))
     }
 
+    /// Waits for a session created in the background to show up.
+    pub(crate) fn wait_for_session(&self, name: &str) -> Result<()> {
+        for _ in 0..SESSION_POLLS {
+            if self.session_exists(name) {
+                return Ok(());
+            }
+            sleep(SESSION_POLL_INTERVAL);
+        }
+        bail!("Session {} did not start", name)
+    }
+
     pub(crate) fn session_exists(&self, name: &str) -> bool {
         self.cmd_runner
             .run(&cmd_basic!(
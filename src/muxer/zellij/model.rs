@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 
 use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
 use miette::{bail, Result};
 
-use crate::common::config::{Command, FlexDirection, Pane, Session, Window};
+use crate::common::config::{
+    version::VERSION, Command, FlexDirection, Pane, Session, Size, Window,
+};
 use crate::common::path::relative_path;
 
 impl Display for FlexDirection {
@@ -40,6 +42,7 @@ impl Command {
         Self {
             command: cmd.to_string(),
             args,
+            ..Default::default()
         }
     }
 }
@@ -53,7 +56,7 @@ impl Session {
             .push(KdlEntry::new_prop("cwd", KdlValue::String(cwd.to_string())));
 
         let mut tabs_doc = KdlDocument::new();
-        for window in &self.windows {
+        for window in self.active_windows() {
             tabs_doc.nodes_mut().push(window.as_kdl()?);
         }
 
@@ -78,11 +81,19 @@ impl Session {
         let window_nodes = extract_child_nodes(layout_node, "tab");
 
         Self {
+            version: VERSION,
             name: name.to_string(),
             path: path.clone(),
             startup: vec![],
             shutdown: vec![],
+            pre_start: vec![],
+            post_start: vec![],
+            on_attach: vec![],
+            on_detach: vec![],
             env: HashMap::new(),
+            vars: HashMap::new(),
+            lint: HashMap::new(),
+            extends: None,
             windows: Window::from_kdl(&window_nodes, &path),
         }
     }
@@ -95,15 +106,32 @@ impl Window {
             "name",
             KdlValue::String(self.name.to_string()),
         ));
+        let (flex_direction, panes) = match &self.layout {
+            Some(layout) => layout.arrange(&self.active_panes()),
+            None => self.flex_panes(),
+        };
         tab_node.entries_mut().push(KdlEntry::new_prop(
             "split_direction",
-            KdlValue::from(self.flex_direction.to_string()),
+            KdlValue::from(flex_direction.to_string()),
         ));
+        if self.focus {
+            tab_node
+                .entries_mut()
+                .push(KdlEntry::new_prop("focus", KdlValue::Bool(true)));
+        }
+        if self.path != "." {
+            tab_node.entries_mut().push(KdlEntry::new_prop(
+                "cwd",
+                KdlValue::String(self.path.clone()),
+            ));
+        }
 
-        if !self.panes.is_empty() {
+        if !panes.is_empty() {
             let mut panes_doc = KdlDocument::new();
-            for pane in &self.panes {
-                panes_doc.nodes_mut().push(pane.as_kdl(&self.panes)?);
+            for pane in &panes {
+                panes_doc
+                    .nodes_mut()
+                    .push(pane.as_kdl(&panes, &self.env, &self.pane_commands)?);
             }
 
             tab_node.set_children(panes_doc);
@@ -132,7 +160,16 @@ impl Window {
                 Window {
                     name,
                     flex_direction,
+                    layout: None,
+                    grid: None,
+                    path: ".".to_string(),
+                    env: HashMap::new(),
+                    pane_commands: vec![],
+                    startup: vec![],
+                    shutdown: vec![],
                     panes,
+                    focus: is_focused(window_node),
+                    when: None,
                 }
             })
             .collect()
@@ -140,23 +177,30 @@ impl Window {
 }
 
 impl Pane {
-    pub fn as_kdl(&self, siblings: &[Pane]) -> Result<KdlNode> {
+    pub fn as_kdl(
+        &self,
+        siblings: &[Pane],
+        env: &HashMap<String, String>,
+        pane_commands: &[Command],
+    ) -> Result<KdlNode> {
         let mut pane_node = KdlNode::new("pane");
+        let mut env = env.clone();
+        env.extend(self.env.clone());
 
-        let percentage = self.calculate_percentage(siblings)?;
         pane_node
             .entries_mut()
-            .push(KdlEntry::new_prop("size", KdlValue::String(percentage)));
-        if !self.panes.is_empty() {
+            .push(KdlEntry::new_prop("size", self.kdl_size(siblings)?));
+        let panes = self.active_panes();
+        if !panes.is_empty() {
             let mut children_doc = KdlDocument::new();
             pane_node.entries_mut().push(KdlEntry::new_prop(
                 "split_direction",
                 KdlValue::from(self.flex_direction.to_string()),
             ));
-            for child_pane in &self.panes {
+            for child_pane in &panes {
                 children_doc
                     .nodes_mut()
-                    .push(child_pane.as_kdl(&self.panes)?);
+                    .push(child_pane.as_kdl(&panes, &env, pane_commands)?);
             }
             pane_node.set_children(children_doc);
         } else {
@@ -166,6 +210,11 @@ impl Pane {
                     KdlValue::String(self.name.clone().unwrap()),
                 ));
             };
+            if self.focus {
+                pane_node
+                    .entries_mut()
+                    .push(KdlEntry::new_prop("focus", KdlValue::Bool(true)));
+            }
             if self.path != "." {
                 pane_node.entries_mut().push(KdlEntry::new_prop(
                     "cwd",
@@ -173,8 +222,20 @@ impl Pane {
                 ));
             };
 
-            for command in &self.commands {
+            if self.wait_for.is_some()
+                || pane_commands
+                    .iter()
+                    .chain(self.commands.iter())
+                    .any(|command| command.wait_for.is_some())
+            {
+                log::warn!("zellij starts all panes at once, ignoring wait_for of pane commands");
+            }
+            for command in &self.pane_commands(&env, pane_commands) {
                 pane_node.push(KdlEntry::new_prop("command", command.command.clone()));
+                if !command.enter {
+                    // the closest zellij has to a typed but not yet run command
+                    pane_node.push(KdlEntry::new_prop("start_suspended", KdlValue::Bool(true)));
+                }
 
                 if !command.args.is_empty() {
                     let mut args_node = KdlNode::new("args");
@@ -196,8 +257,16 @@ impl Pane {
     }
 
     pub(crate) fn from_kdl(pane_nodes: &[&KdlNode], session_path: &String) -> Vec<Pane> {
+        // fixed sizes are kept, percentages are turned into flex ratios
+        let fixed_size = |node: &KdlNode| {
+            node.get("size")
+                .and_then(|value| value.as_integer())
+                .map(|cells| Size::Cells(cells as usize))
+        };
+
         let size_strings: Vec<&str> = pane_nodes
             .iter()
+            .filter(|n| fixed_size(n).is_none())
             .map(|n| {
                 n.get("size")
                     .and_then(|value| value.as_string())
@@ -205,12 +274,16 @@ impl Pane {
             })
             .collect();
 
-        let ratios = calculate_ratios(&size_strings);
+        let mut ratios = calculate_ratios(&size_strings).into_iter();
 
         pane_nodes
             .iter()
-            .zip(ratios.iter())
-            .map(|(node, &flex)| {
+            .map(|node| {
+                let size = fixed_size(node);
+                let flex = match size {
+                    Some(_) => 1,
+                    None => ratios.next().unwrap_or(1),
+                };
                 let full_path: String = node
                     .get("cwd")
                     .and_then(|value| value.as_string().map(|s| s.to_string()))
@@ -244,6 +317,9 @@ impl Pane {
 
                 Pane {
                     flex,
+                    size,
+                    min_size: None,
+                    max_size: None,
                     flex_direction,
                     name,
                     path,
@@ -252,15 +328,132 @@ impl Pane {
                     env: HashMap::new(),
                     panes,
                     zoom: false,
+                    focus: is_focused(node),
+                    wait_for: None,
+                    span: None,
+                    shell: None,
+                    exec: false,
+                    when: None,
                 }
             })
             .collect()
     }
 
+    // zellij layouts have no notion of pane environments, so pane commands
+    // are wrapped in `env` to get the variables into the pane process.
+    fn pane_commands(
+        &self,
+        env: &HashMap<String, String>,
+        pane_commands: &[Command],
+    ) -> Vec<Command> {
+        let own_commands = self.leaf_commands(pane_commands);
+        if env.is_empty() {
+            return own_commands;
+        }
+
+        let env_args: Vec<String> = env
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+
+        let shell = Command {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), "exec \"${SHELL:-sh}\"".to_string()],
+            ..Default::default()
+        };
+
+        let commands = if own_commands.is_empty() {
+            vec![shell]
+        } else {
+            own_commands
+        };
+
+        commands
+            .into_iter()
+            .map(|command| Command {
+                command: "env".to_string(),
+                args: env_args
+                    .iter()
+                    .cloned()
+                    .chain(std::iter::once(command.command))
+                    .chain(command.args)
+                    .collect(),
+                ..command
+            })
+            .collect()
+    }
+
+    // A zellij pane runs a single command, so window pane commands and delays are chained
+    // with the pane's own commands in one shell. The shell stays open once they are done if
+    // the pane has no commands of its own or a custom shell, unless it execs its commands.
+    fn leaf_commands(&self, pane_commands: &[Command]) -> Vec<Command> {
+        let delayed = self.commands.iter().any(|command| command.delay_ms > 0);
+        if self.shell.is_none() && pane_commands.is_empty() && !delayed {
+            return self.commands.clone();
+        }
+        if let (Some(shell), [], []) = (&self.shell, pane_commands, self.commands.as_slice()) {
+            return vec![Command {
+                command: shell.clone(),
+                ..Default::default()
+            }];
+        }
+
+        let mut script: Vec<String> = vec![];
+        for command in pane_commands.iter().chain(self.commands.iter()) {
+            script.push(command.to_string());
+            if command.delay_ms > 0 {
+                script.push(format!(
+                    "sleep {}.{:03}",
+                    command.delay_ms / 1000,
+                    command.delay_ms % 1000
+                ));
+            }
+        }
+        if !self.exec && (self.shell.is_some() || self.commands.is_empty()) {
+            script.push(format!(
+                "exec {}",
+                self.shell.as_deref().unwrap_or("\"${SHELL:-sh}\"")
+            ));
+        }
+
+        vec![Command {
+            command: self.shell.clone().unwrap_or_else(|| "sh".to_string()),
+            args: vec!["-c".to_string(), script.join("; ")],
+            enter: pane_commands
+                .iter()
+                .chain(self.commands.iter())
+                .all(|command| command.enter),
+            ..Default::default()
+        }]
+    }
+
+    fn kdl_size(&self, siblings: &[Pane]) -> Result<KdlValue> {
+        if self.min_size.is_some() || self.max_size.is_some() {
+            log::debug!("zellij layouts do not support min_size and max_size, ignoring them");
+        }
+        match self.size {
+            Some(Size::Cells(cells)) => Ok(KdlValue::Integer(cells as i128)),
+            Some(Size::Percent(percent)) => Ok(KdlValue::String(format!("{}%", percent))),
+            None => self.calculate_percentage(siblings).map(KdlValue::String),
+        }
+    }
+
+    // Flex panes share the percentage not taken by siblings with a percentage size.
     fn calculate_percentage(&self, siblings: &[Pane]) -> Result<String> {
-        let total_flex: f64 = siblings.iter().map(|p| p.flex as f64).sum();
+        let flexible = siblings.iter().filter(|p| p.size.is_none());
+        let total_flex: f64 = flexible.map(|p| p.flex as f64).sum();
+        let remaining: f64 = 100.0
+            - siblings
+                .iter()
+                .filter_map(|p| match p.size {
+                    Some(Size::Percent(percent)) => Some(percent as f64),
+                    _ => None,
+                })
+                .sum::<f64>();
         if total_flex > 0.0 {
-            let percentage = ((self.flex as f64 / total_flex) * 100.0).round();
+            let percentage = ((self.flex as f64 / total_flex) * remaining.max(0.0)).round();
             Ok(format!("{}%", percentage))
         } else {
             bail!("Total flex value is zero, cannot calculate percentage")
@@ -298,3 +491,9 @@ pub(crate) fn find_entry_value<'a>(node: &'a KdlNode, name: &str) -> Option<&'a
         }
     })
 }
+
+fn is_focused(node: &KdlNode) -> bool {
+    node.get("focus")
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false)
+}
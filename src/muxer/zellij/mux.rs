@@ -1,14 +1,17 @@
-use std::{env::temp_dir, fs::OpenOptions, io::Write, rc::Rc};
+use std::{collections::HashMap, env::temp_dir, fs::OpenOptions, io::Write, path::Path, rc::Rc};
 
 use miette::{bail, IntoDiagnostic, Result};
 
 use crate::{
-    app::manager::session::manager::LAIO_CONFIG,
+    app::manager::session::manager::{LAIO_CONFIG, LAIO_VARS},
     common::{
         cmd::{Runner, ShellRunner},
         config::Session,
-        muxer::{Client, Multiplexer},
-        path::{resolve_symlink, sanitize_filename, sanitize_path, to_absolute_path},
+        muxer::{
+            client::{save_vars, saved_vars},
+            Client, Multiplexer,
+        },
+        path::{resolve_symlink, sanitize_filename, to_absolute_path},
     },
 };
 
@@ -66,28 +69,53 @@ impl<R: Runner> Multiplexer for Zellij<R> {
         }
 
         if !skip_cmds {
-            self.client.run_commands(&session.startup, &session.path)?;
+            self.client.run_hooks(&session.pre_start, session, config)?;
         }
 
-        let cwd = session
-            .windows
-            .first()
-            .and_then(|window| window.first_leaf_path())
-            .map(|path| sanitize_path(path, &session.path))
-            .unwrap_or(session.path.clone());
-
-        let layout: String = self.session_to_layout(cwd.as_str(), session, skip_cmds)?;
-        let _res: () = self.client.create_session_with_layout(
-            &session.name,
-            config,
-            layout.as_str(),
-            skip_attach,
-        )?;
+        if !skip_cmds {
+            self.client.run_startup_commands(session)?;
+            if !session.on_attach.is_empty() || !session.on_detach.is_empty() {
+                log::warn!(
+                    "zellij has no attach and detach hooks, ignoring on_attach and on_detach"
+                );
+            }
+            for window in session.active_windows() {
+                self.client
+                    .run_window_commands(&window.startup, session, window)?;
+            }
+        }
+
+        // tab and pane paths in the layout are relative to the session path
+        let layout: String = self.session_to_layout(&session.path, session, skip_cmds)?;
+        let mut env = session.env.clone();
+        if !session.vars.is_empty() {
+            env.insert(LAIO_VARS.to_string(), save_vars(session));
+        }
+        // created in the background so post_start runs before attaching
+        let _res: () =
+            self.client
+                .create_session_with_layout(&session.name, config, layout.as_str(), &env)?;
+        self.client.wait_for_session(&session.name)?;
+
+        if !skip_cmds {
+            self.client
+                .run_hooks(&session.post_start, session, config)?;
+        }
 
+        if !skip_attach {
+            self.client.attach(&session.name)?;
+        }
         Ok(())
     }
 
-    fn stop(&self, name: &Option<String>, skip_cmds: bool, stop_all: bool) -> Result<()> {
+    fn stop(
+        &self,
+        name: &Option<String>,
+        config_dir: &Path,
+        vars: &HashMap<String, String>,
+        skip_cmds: bool,
+        stop_all: bool,
+    ) -> Result<()> {
         let current_session_name = self.client.current_session_name()?;
         log::debug!("Current session name: {}", current_session_name);
 
@@ -109,7 +137,12 @@ impl<R: Runner> Multiplexer for Zellij<R> {
 
                 if self.is_laio_session(&name)? {
                     log::debug!("Closing session: {:?}", name);
-                    self.stop(&Some(name.to_string()), skip_cmds, false)?;
+                    // one failing session does not keep the others running
+                    if let Err(e) =
+                        self.stop(&Some(name.to_string()), config_dir, vars, skip_cmds, false)
+                    {
+                        log::error!("Failed to stop session {}: {:?}", name, e);
+                    }
                 }
             }
             if !self.client.is_inside_session() {
@@ -126,9 +159,13 @@ impl<R: Runner> Multiplexer for Zellij<R> {
                     Ok(config) => {
                         log::debug!("Config: {:?}", config);
 
-                        let session =
-                            Session::from_config(&resolve_symlink(&to_absolute_path(&config)?)?)?;
-                        self.client.run_commands(&session.shutdown, &session.path)
+                        let vars = saved_vars(self.client.getenv(&name, LAIO_VARS), vars);
+                        let session = Session::from_config(
+                            &resolve_symlink(&to_absolute_path(&config)?)?,
+                            config_dir,
+                            &vars,
+                        )?;
+                        self.client.run_shutdown_commands(&session)
                     }
                     Err(e) => {
                         log::warn!("LAIO_CONFIG environment variable not found: {:?}", e);
@@ -141,7 +178,7 @@ impl<R: Runner> Multiplexer for Zellij<R> {
             }
         })();
 
-        let stop_result = self.client.stop_session(name.as_str()).map_err(Into::into);
+        let stop_result = self.client.stop_session(name.as_str());
 
         result.and(stop_result)
     }
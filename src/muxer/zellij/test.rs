@@ -1,4 +1,11 @@
-use std::{env::current_dir, fs::read_to_string, path::PathBuf, str::FromStr};
+use std::{
+    collections::HashMap,
+    env::current_dir,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use crate::common::{
     cmd::{
@@ -13,12 +20,14 @@ use serde_valid::json::Value;
 
 use super::Zellij;
 
+const CONFIG_DIR: &str = "src/common/config/test";
+
 #[test]
 fn mux_start_session() -> Result<()> {
     let path = PathBuf::from_str("src/common/config/test/valid.yaml").unwrap();
     let path_str = path.to_string_lossy().into_owned();
 
-    let session = Session::from_config(&path).unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new()).unwrap();
     let mut cmd_unit = MockCmdUnitMock::new();
     let mut cmd_string = MockCmdStringMock::new();
     let mut cmd_bool = MockCmdBoolMock::new();
@@ -30,21 +39,29 @@ fn mux_start_session() -> Result<()> {
             let path_str = path_str.clone();
             move |cmd| matches!(cmd,
               Type::Forget(content) if
-              content.starts_with(&format!("LAIO_CONFIG={} zellij --session valid --new-session-with-layout", path_str)))
+              content.starts_with(&format!("FOO='BAR' LAIO_CONFIG={} nohup zellij --session valid --new-session-with-layout", path_str)))
         })
         .returning(|_| Ok(()));
 
-    cmd_bool
+    // attaching once the session exists and post_start ran
+    cmd_unit
         .expect_run()
         .times(1)
+        .withf(|cmd| matches!(cmd, Type::Forget(content) if content == "zellij attach valid "))
+        .returning(|_| Ok(()));
+
+    let created = AtomicBool::new(false);
+    cmd_bool
+        .expect_run()
+        .times(2)
         .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "zellij list-sessions --short | grep \"valid\""))
-        .returning(|_| Ok(false));
+        .returning(move |_| Ok(created.swap(true, Ordering::SeqCst)));
 
     cmd_string
         .expect_run()
         .times(2)
         .withf(
-            |cmd| matches!(cmd, Type::Verbose(content) if vec!["date", "echo Hi"].contains(&content.as_str())),
+            |cmd| matches!(cmd, Type::Verbose(content) if ["date", "echo Hi"].contains(&content.as_str())),
         )
         .returning(|_| Ok("".to_string()));
 
@@ -56,7 +73,7 @@ fn mux_start_session() -> Result<()> {
 
     let zellij = Zellij::new_with_runner(runner);
 
-    let _result = zellij.start(&session, &path_str, false, false)?;
+    zellij.start(&session, &path_str, false, false)?;
 
     Ok(())
 }
@@ -91,6 +108,12 @@ fn mux_stop_session() -> Result<()> {
     cmd_string
         .expect_run()
         .times(1)
+        .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "printenv LAIO_VARS || true"))
+        .returning(|_| Ok("".to_string()));
+
+    cmd_string
+        .expect_run()
+        .times(2)
         .withf(|cmd| matches!(cmd, Type::Basic(content) if content == "printenv ZELLIJ"))
         .returning(|_| Ok("0".to_string()));
 
@@ -109,7 +132,7 @@ fn mux_stop_session() -> Result<()> {
     cmd_string
         .expect_run()
         .times(2)
-        .withf(|cmd| matches!(cmd, Type::Verbose(content) if vec!["date", "echo Bye"].contains(&content.as_str())))
+        .withf(|cmd| matches!(cmd, Type::Verbose(content) if ["date", "echo Bye"].contains(&content.as_str())))
         .returning(|_| Ok("".to_string()));
 
     let runner = RunnerMock {
@@ -120,7 +143,13 @@ fn mux_stop_session() -> Result<()> {
 
     let zellij = Zellij::new_with_runner(runner);
 
-    let _result = zellij.stop(&Some("valid".to_string()), false, false)?;
+    zellij.stop(
+        &Some("valid".to_string()),
+        Path::new(CONFIG_DIR),
+        &HashMap::new(),
+        false,
+        false,
+    )?;
 
     Ok(())
 }
@@ -181,3 +210,163 @@ fn mux_get_session() -> Result<()> {
     assert_eq!(valid_yaml, expected_session_yaml);
     Ok(())
 }
+
+#[test]
+fn session_as_kdl_pane_env() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/valid.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+
+    assert!(kdl.contains(r#"cwd="src" command="env""#));
+    assert!(kdl.contains(r#"args "FOO=PANE" "echo" "hello again""#));
+    Ok(())
+}
+
+#[test]
+fn session_as_kdl_when() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/when.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+
+    assert!(kdl.contains(r#"tab name="code""#));
+    assert!(!kdl.contains(r#"tab name="docker""#));
+    assert!(kdl.contains(r#"pane size="50%" name="editor""#));
+    assert!(!kdl.contains(r#"name="docker""#));
+    Ok(())
+}
+
+#[test]
+fn session_as_kdl_sizes() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/sizes.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+
+    assert!(kdl.contains(r#"pane size=30 name="tree""#));
+    assert!(kdl.contains(r#"pane size="67%" name="editor""#));
+    assert!(kdl.contains(r#"pane size="33%" name="outline""#));
+    assert!(kdl.contains(r#"pane size="25%" name="status""#));
+    assert!(kdl.contains(r#"pane size="75%" name="tail""#));
+    Ok(())
+}
+
+#[test]
+fn session_as_kdl_focus() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/valid.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+
+    assert!(kdl.contains(r#"tab name="infrastructure" split_direction="horizontal" focus=true"#));
+    assert!(kdl.contains(r#"pane size="50%" focus=true cwd="two""#));
+    assert_eq!(kdl.matches("focus=true").count(), 2);
+    Ok(())
+}
+
+#[test]
+fn session_as_kdl_window_defaults() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/window_defaults.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+
+    assert!(kdl.contains(r#"tab name="python" split_direction="vertical" cwd="app""#));
+    assert!(
+        kdl.contains(r#"args "VIRTUAL_ENV=.venv" "sh" "-c" "source .venv/bin/activate; pytest""#)
+    );
+    assert!(kdl.contains(r#"cwd="/var/log" command="env""#));
+    assert!(kdl.contains(
+        r#"args "VIRTUAL_ENV=none" "sh" "-c" "source .venv/bin/activate; exec \"${SHELL:-sh}\"""#
+    ));
+    Ok(())
+}
+
+#[test]
+fn mux_start_session_window_hooks() -> Result<()> {
+    let path = PathBuf::from_str("src/common/config/test/hooks.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let mut cmd_unit = MockCmdUnitMock::new();
+    let mut cmd_string = MockCmdStringMock::new();
+    let mut cmd_bool = MockCmdBoolMock::new();
+
+    let created = AtomicBool::new(false);
+    cmd_bool
+        .expect_run()
+        .returning(move |_| Ok(created.swap(true, Ordering::SeqCst)));
+    cmd_unit.expect_run().times(1).returning(|_| Ok(()));
+    cmd_string
+        .expect_run()
+        .times(1)
+        .withf(|cmd| {
+            matches!(cmd, Type::Verbose(content) if content == "kubectl port-forward svc/api 8080")
+        })
+        .returning(|_| {
+            assert_eq!(std::env::var("KUBE_CONTEXT").as_deref(), Ok("dev"));
+            Ok("".to_string())
+        });
+
+    let zellij = Zellij::new_with_runner(RunnerMock {
+        cmd_unit,
+        cmd_string,
+        cmd_bool,
+    });
+    zellij.start(&session, "hooks.yaml", true, false)?;
+    Ok(())
+}
+
+#[test]
+fn session_as_kdl_command_options() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/commands.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+    assert!(kdl.contains(r#"command="git" start_suspended=true"#));
+    assert!(kdl.contains(r#""it's done""#));
+    assert!(kdl.contains(r#"command="sh""#));
+    assert!(kdl.contains(r#"args "-c" "echo Enter; sleep 1.500; ls""#));
+    Ok(())
+}
+
+#[test]
+fn session_as_kdl_exec() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/exec.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+    assert!(kdl.contains(r#"command="/usr/bin/fish""#));
+    assert!(kdl.contains(r#"args "-c" "nvim; exec /usr/bin/fish""#));
+    assert!(kdl.contains(r#"name="logs" command="tail""#));
+    assert!(kdl.contains(r#"command="/bin/bash""#));
+    assert!(kdl.contains(r#"args "-c" "htop""#));
+    assert!(kdl.contains(r#"command="btop""#));
+    Ok(())
+}
+
+#[test]
+fn session_as_kdl_layout_preset() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/layouts.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+    assert!(kdl.contains(r#"tab name="code" split_direction="vertical""#));
+    assert!(kdl.contains(r#"tab name="monitoring" split_direction="horizontal""#));
+    assert!(kdl.contains(r#"size="67%" name="editor""#));
+    assert!(kdl.contains(r#"size="33%" split_direction="horizontal""#));
+    Ok(())
+}
+
+#[test]
+fn session_as_kdl_grid() -> Result<()> {
+    let path = PathBuf::from_str("./src/common/config/test/grid.yaml").unwrap();
+    let session = Session::from_config(&path, Path::new(CONFIG_DIR), &HashMap::new())?;
+
+    let kdl = session.as_kdl("/tmp")?.to_string();
+    assert!(kdl.contains(r#"tab name="monitoring" split_direction="horizontal""#));
+    assert!(kdl.contains(r#"tab name="dashboard" split_direction="vertical""#));
+    assert!(kdl.contains(r#"size="33%" name="main""#));
+    assert!(kdl.contains(r#"size="67%" split_direction="horizontal""#));
+    Ok(())
+}